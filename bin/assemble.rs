@@ -2,12 +2,16 @@ use crate::error::RuntimeError;
 use rust_snapshot::app::config::Manifest;
 use rust_snapshot::app::error::AppError;
 use rust_snapshot::app::App;
-use rust_snapshot::encoding::Encoding;
+use rust_snapshot::encoding::{
+    AlignReference, CrossFade, DedupMode, Deduplicator, Deflicker, Encoding, ExportPipeline,
+    FrameAverage, FrameDuplicate, IncrementalExport, OverlayStage, SmoothingMethod, Stabilizer,
+};
 use rust_snapshot::resources::ResourceFolder;
 use std::ffi::OsStr;
 use std::fs;
 use std::path::PathBuf;
 use std::process::exit;
+use std::str::FromStr;
 
 fn main() -> Result<(), RuntimeError> {
     let args = std::env::args().collect::<Vec<String>>();
@@ -23,16 +27,137 @@ fn main() -> Result<(), RuntimeError> {
     let input = ResourceFolder::new(&manifest.config.output_folder).require_existing()?;
     let full_output = get_full_output_path(&manifest)?;
 
-    encoder.export_webm(
-        &input,
-        "%d_*",
-        &full_output,
-        manifest.export.export_framerate,
-    )?;
+    // Frames are always staged, as they may be spread over dated subfolders
+    let mut pipeline = create_pipeline(&manifest)?;
+    let mut frames = enumerate_frames(&input)?;
+    let mut incremental = create_incremental(&manifest, &input, &full_output)?;
+    if let Some(incremental) = incremental.as_mut() {
+        frames = incremental.take_new_frames(&frames);
+        println!("incremental: {} new frames", frames.len());
+    }
+    if manifest.export.dedup {
+        let mode = DedupMode::from_str(&manifest.export.dedup_mode)?;
+        let report = Deduplicator::new(manifest.export.dedup_threshold, mode).dedup(&frames)?;
+        let report_path = match manifest.export.dedup_report.as_ref() {
+            Some(path) => PathBuf::from(path),
+            None => PathBuf::from(format!("{}.dedup.txt", &full_output)),
+        };
+        report.save(&report_path)?;
+        println!(
+            "dedup: {} (see {})",
+            report.summary(),
+            report_path.display()
+        );
+        frames = report.kept;
+    }
+
+    let staging = get_staging_path(&manifest, &input)?;
+    match incremental.as_mut() {
+        Some(incremental) => encoder.export_incremental(
+            &frames,
+            &mut pipeline,
+            &staging,
+            incremental,
+            &full_output,
+            manifest.export.export_framerate,
+        )?,
+        None => encoder.export_frames(
+            &frames,
+            &mut pipeline,
+            &staging,
+            &full_output,
+            manifest.export.export_framerate,
+        )?,
+    }
 
     Ok(())
 }
 
+/// The export stages enabled in the manifest, in the order they are applied
+fn create_pipeline(manifest: &Manifest) -> Result<ExportPipeline, RuntimeError> {
+    let mut pipeline = ExportPipeline::new();
+    if manifest.export.stabilize {
+        let reference = AlignReference::from_str(&manifest.export.stabilize_reference)?;
+        pipeline.add(
+            Stabilizer::new(reference)
+                .with_rotation(manifest.export.stabilize_rotation)
+                .with_max_shift(manifest.export.stabilize_max_shift)
+                .with_crop(manifest.export.stabilize_crop),
+        );
+    }
+    if manifest.export.deflicker {
+        let method = SmoothingMethod::from_str(&manifest.export.deflicker_method)?;
+        pipeline.add(Deflicker::new(manifest.export.deflicker_window, method));
+    }
+    if manifest.export.average_frames > 1 {
+        pipeline.add(FrameAverage::new(manifest.export.average_frames));
+    }
+    if manifest.export.blend_frames > 0 {
+        pipeline.add(CrossFade::new(manifest.export.blend_frames));
+    }
+    if manifest.export.duplicate_frames > 1 {
+        pipeline.add(FrameDuplicate::new(manifest.export.duplicate_frames));
+    }
+    if manifest.overlay.overlay_export {
+        pipeline.add(OverlayStage::new(App::create_overlay(manifest)?));
+    }
+    Ok(pipeline)
+}
+
+/// The record of previously encoded frames, if incremental export is enabled
+fn create_incremental(
+    manifest: &Manifest,
+    input: &ResourceFolder,
+    full_output: &str,
+) -> Result<Option<IncrementalExport>, RuntimeError> {
+    if !manifest.export.incremental {
+        return Ok(None);
+    }
+    // Each segment runs a fresh pipeline, so stages which depend on earlier frames would
+    // restart at every segment and jump at each join
+    let export = &manifest.export;
+    if export.stabilize || export.deflicker || export.average_frames > 1 {
+        return Err(RuntimeError::Failed(
+            "incremental export can't be combined with stabilize, deflicker or average_frames"
+                .to_string(),
+        ));
+    }
+    let state_path = match manifest.export.state_file.as_ref() {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(format!("{}.state", full_output)),
+    };
+    let segment_folder = match manifest.export.segment_folder.as_ref() {
+        Some(path) => PathBuf::from(path),
+        None => input.path("segments")?,
+    };
+    // Segments encoded with different settings can't be joined; start again if they change
+    let fingerprint = format!(
+        "{:x}",
+        md5::compute(format!("{:?}{:?}", manifest.export, manifest.overlay))
+    );
+    Ok(Some(IncrementalExport::open(
+        &state_path,
+        &segment_folder,
+        &fingerprint,
+    )?))
+}
+
+/// The captured frames, in capture order
+fn enumerate_frames(input: &ResourceFolder) -> Result<Vec<PathBuf>, RuntimeError> {
+    Ok(input
+        .walk_files()?
+        .into_iter()
+        .filter(|path| path.extension() == Some(OsStr::new("png")))
+        .collect())
+}
+
+fn get_staging_path(manifest: &Manifest, input: &ResourceFolder) -> Result<PathBuf, RuntimeError> {
+    match manifest.export.staging_folder.as_ref() {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => Ok(input.path("staging")?),
+    }
+}
+
 fn get_full_output_path(manifest: &Manifest) -> Result<String, RuntimeError> {
     let mut output = PathBuf::from(&manifest.export.export_file);
     let filename = output
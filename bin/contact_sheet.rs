@@ -0,0 +1,130 @@
+use crate::error::RuntimeError;
+use chrono::NaiveDate;
+use rust_snapshot::app::config::Manifest;
+use rust_snapshot::encoding::{ContactSheet, SheetFrame};
+use rust_snapshot::resources::ResourceFolder;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+fn main() -> Result<(), RuntimeError> {
+    let args = std::env::args().collect::<Vec<String>>();
+    if args.len() < 2 || args.len() > 4 {
+        println!("usage: {} [SETTINGS] [FROM] [TO]", args[0]);
+        println!("  writes one sheet per day, or one sheet for FROM..=TO (YYYY-MM-DD)");
+        exit(1);
+    }
+
+    let settings = fs::read_to_string(&args[1])?;
+    let manifest: Manifest = toml::from_str(settings.as_str())?;
+    let config = &manifest.contact_sheet;
+
+    let input = ResourceFolder::new(&manifest.config.output_folder).require_existing()?;
+    let output = match config.output_folder.as_ref() {
+        Some(folder) => ResourceFolder::new(folder),
+        None => ResourceFolder::new(&input.path("contact_sheets")?.to_string_lossy()),
+    }
+    .require()?;
+
+    let sheet = ContactSheet::new(config.columns, config.thumbnail_width)
+        .with_max_frames(config.max_frames)
+        .with_caption_format(&config.caption_format);
+    let frames = SheetFrame::from_paths(&enumerate_frames(&input)?);
+
+    if args.len() > 2 {
+        let from = parse_date(&args[2])?;
+        let to = match args.get(3) {
+            Some(date) => parse_date(date)?,
+            None => from,
+        };
+        let frames: Vec<SheetFrame> = frames
+            .into_iter()
+            .filter(|frame| frame.date() >= from && frame.date() <= to)
+            .collect();
+        let title = if from == to {
+            format!("{}", from)
+        } else {
+            format!("{} - {}", from, to)
+        };
+        let name = format!("{}_{}.{}", from, to, config.format);
+        write_sheet(&sheet, &title, &frames, output.path(&name)?)?;
+        return Ok(());
+    }
+
+    for (date, frames) in ContactSheet::group_by_day(frames).iter() {
+        let name = format!("{}.{}", date, config.format);
+        write_sheet(&sheet, &format!("{}", date), frames, output.path(&name)?)?;
+    }
+    Ok(())
+}
+
+fn write_sheet(
+    sheet: &ContactSheet,
+    title: &str,
+    frames: &[SheetFrame],
+    path: PathBuf,
+) -> Result<(), RuntimeError> {
+    if frames.is_empty() {
+        println!("{}: no frames", title);
+        return Ok(());
+    }
+    sheet.render(title, frames)?.save(&path)?;
+    println!("{}: {} frames -> {}", title, frames.len(), path.display());
+    Ok(())
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, RuntimeError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| RuntimeError::Failed(format!("invalid date '{}'; use YYYY-MM-DD", value)))
+}
+
+/// The captured frames
+fn enumerate_frames(input: &ResourceFolder) -> Result<Vec<PathBuf>, RuntimeError> {
+    Ok(input
+        .walk_files()?
+        .into_iter()
+        .filter(|path| path.extension() == Some(OsStr::new("png")))
+        .collect())
+}
+
+mod error {
+    use rust_snapshot::encoding::error::EncodingError;
+    use rust_snapshot::resources::ResourceError;
+    use std::io;
+
+    #[derive(Debug)]
+    pub enum RuntimeError {
+        Failed(String),
+    }
+
+    impl From<ResourceError> for RuntimeError {
+        fn from(err: ResourceError) -> Self {
+            RuntimeError::Failed(format!("{}", err))
+        }
+    }
+
+    impl From<io::Error> for RuntimeError {
+        fn from(err: io::Error) -> Self {
+            RuntimeError::Failed(format!("{}", err))
+        }
+    }
+
+    impl From<EncodingError> for RuntimeError {
+        fn from(err: EncodingError) -> Self {
+            RuntimeError::Failed(format!("{}", err))
+        }
+    }
+
+    impl From<image::ImageError> for RuntimeError {
+        fn from(err: image::ImageError) -> Self {
+            RuntimeError::Failed(format!("failed to write sheet: {}", err))
+        }
+    }
+
+    impl From<toml::de::Error> for RuntimeError {
+        fn from(err: toml::de::Error) -> Self {
+            RuntimeError::Failed(format!("invalid manifest: {}", err))
+        }
+    }
+}
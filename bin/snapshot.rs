@@ -1,6 +1,7 @@
 use crate::error::RuntimeError;
 use rust_snapshot::app::config::Manifest;
 use rust_snapshot::app::App;
+use rust_snapshot::resources::install_shutdown_handler;
 use std::fs;
 use std::process::exit;
 
@@ -14,6 +15,9 @@ fn main() -> Result<(), RuntimeError> {
     let settings = fs::read_to_string(&args[1])?;
     let manifest: Manifest = toml::from_str(settings.as_str())?;
 
+    // Finish the current frame and shut down cleanly on ctrl-c or a service stop
+    install_shutdown_handler()?;
+
     let mut app = App::new(manifest)?;
     app.run()?;
 
@@ -2,18 +2,23 @@ pub use self::error::CaptureError;
 use self::helpers::{alloc_frame, as_error, destroy_frame};
 use ffmpeg_sys::AVPixelFormat::*;
 use ffmpeg_sys::*;
-use std::ffi::{c_void, CString};
+use std::ffi::{c_void, CStr, CString};
 use std::intrinsics::transmute;
 use std::mem::size_of;
 use std::os::raw::c_int;
 use std::ptr::{null, null_mut};
 
+#[derive(Clone)]
 pub struct CaptureSettings {
+    /// The libav input format; leave empty to let libav detect it from the device.
     pub backend: String,
     pub device: String,
     pub framerate: u32,
     pub resolution: (u32, u32),
     pub pixel_format: String,
+
+    /// Any extra options for the input format, eg. ("rtsp_transport", "tcp")
+    pub options: Vec<(String, String)>,
 }
 
 impl CaptureSettings {
@@ -55,9 +60,10 @@ impl Capture {
             // Allocate a libav context
             self.context = Some(avformat_alloc_context());
 
-            // The settings must provide a specific backend; eg. avfoundation, v4l2, etc.
+            // The settings should provide a specific backend; eg. avfoundation, v4l2, etc.
             // libav refers to these as 'input formats', but they're just various backends.
-            {
+            // For files and network streams it can be left empty and libav will probe it.
+            if !self.settings.backend.is_empty() {
                 let backend_name = CString::new(self.settings.backend.as_str())?;
                 let input = av_find_input_format(backend_name.as_ptr());
                 if input.is_null() {
@@ -133,7 +139,7 @@ impl Capture {
             let value = CString::new(format!("{}", self.settings.framerate))?;
             av_dict_set(&mut device_options, key.as_ptr(), value.as_ptr(), 0);
         }
-        {
+        if !self.settings.pixel_format.is_empty() {
             let key = CString::new("pixel_format")?;
             let value = CString::new(self.settings.pixel_format.as_str())?;
             av_dict_set(&mut device_options, key.as_ptr(), value.as_ptr(), 0);
@@ -144,6 +150,11 @@ impl Capture {
             let value = CString::new(rez.as_str())?;
             av_dict_set(&mut device_options, key.as_ptr(), value.as_ptr(), 0);
         }
+        for (key, value) in self.settings.options.iter() {
+            let key = CString::new(key.as_str())?;
+            let value = CString::new(value.as_str())?;
+            av_dict_set(&mut device_options, key.as_ptr(), value.as_ptr(), 0);
+        }
         {
             // Attempt to actually open the input device
             let device_name = CString::new(self.settings.device.as_str())?;
@@ -210,9 +221,17 @@ impl Capture {
 
         // Loop through, receiving packets until we have an entire frame.
         loop {
-            if av_read_frame(context, packet) < 0 {
+            let response = av_read_frame(context, packet);
+            if response == AVERROR_EOF {
+                return Err(CaptureError::EndOfStream);
+            }
+            if response == -libc::EAGAIN {
                 continue;
             }
+            if response < 0 {
+                // eg. a dropped network stream, which would otherwise be retried forever
+                return Err(as_error(response, "av_read_frame failed"));
+            }
 
             if (*packet).stream_index != self.videoindex {
                 continue;
@@ -349,6 +368,20 @@ impl Capture {
     }
 }
 
+/// True if libav was built with the named input protocol, eg. http or rtsp
+pub fn has_input_protocol(name: &str) -> bool {
+    let mut opaque: *mut c_void = null_mut();
+    loop {
+        let protocol = unsafe { avio_enum_protocols(&mut opaque, 0) };
+        if protocol.is_null() {
+            return false;
+        }
+        if unsafe { CStr::from_ptr(protocol) }.to_bytes() == name.as_bytes() {
+            return true;
+        }
+    }
+}
+
 mod error {
     use std::ffi::NulError;
     use std::fmt::Formatter;
@@ -358,6 +391,7 @@ mod error {
         NotImplemented,
         InvalidDriver,
         NotReady,
+        EndOfStream,
         InvalidBuffer(String),
         MissingStream(String),
         MissingCodec(String),
@@ -465,6 +499,7 @@ mod tests {
             resolution: size,
             framerate: 24,
             pixel_format: "0rgb".to_string(),
+            options: Vec::new(),
         });
 
         let buffer_size = capture.get_buffer_size().unwrap();
@@ -488,6 +523,7 @@ mod tests {
             resolution: size,
             framerate: 24,
             pixel_format: "0rgb".to_string(),
+            options: Vec::new(),
         });
 
         let buffer_size = capture.get_buffer_size().unwrap();
@@ -0,0 +1,359 @@
+//! Camera control parameters; see ref/mmal/mmal_parameters_camera.h for the meaning
+//! of each of the MMAL_PARAMETER_* values set here.
+use crate::error::CaptureError;
+use mmal_sys as ffi;
+use rascam::SeriousCamera;
+use std::mem;
+use std::ptr::NonNull;
+
+/// The white balance modes supported by the camera; see MMAL_PARAM_AWBMODE_T.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AwbMode {
+    Off,
+    Auto,
+    Sunlight,
+    Cloudy,
+    Shade,
+    Tungsten,
+    Fluorescent,
+    Incandescent,
+    Flash,
+    Horizon,
+    Greyworld,
+}
+
+/// The exposure modes supported by the camera; see MMAL_PARAM_EXPOSUREMODE_T.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExposureMode {
+    Off,
+    Auto,
+    Night,
+    NightPreview,
+    Backlight,
+    Spotlight,
+    Sports,
+    Snow,
+    Beach,
+    VeryLong,
+    FixedFps,
+    AntiShake,
+    Fireworks,
+}
+
+/// The exposure metering modes; see MMAL_PARAM_EXPOSUREMETERINGMODE_T.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MeteringMode {
+    Average,
+    Spot,
+    Backlit,
+    Matrix,
+}
+
+/// The dynamic range compression strength; see MMAL_PARAMETER_DRC_STRENGTH_T.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DrcStrength {
+    Off,
+    Low,
+    Medium,
+    High,
+}
+
+impl AwbMode {
+    fn as_mmal(&self) -> ffi::MMAL_PARAM_AWBMODE_T {
+        match self {
+            AwbMode::Off => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_OFF,
+            AwbMode::Auto => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_AUTO,
+            AwbMode::Sunlight => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_SUNLIGHT,
+            AwbMode::Cloudy => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_CLOUDY,
+            AwbMode::Shade => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_SHADE,
+            AwbMode::Tungsten => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_TUNGSTEN,
+            AwbMode::Fluorescent => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_FLUORESCENT,
+            AwbMode::Incandescent => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_INCANDESCENT,
+            AwbMode::Flash => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_FLASH,
+            AwbMode::Horizon => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_HORIZON,
+            AwbMode::Greyworld => ffi::MMAL_PARAM_AWBMODE_T_MMAL_PARAM_AWBMODE_GREYWORLD,
+        }
+    }
+}
+
+impl ExposureMode {
+    fn as_mmal(&self) -> ffi::MMAL_PARAM_EXPOSUREMODE_T {
+        match self {
+            ExposureMode::Off => ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_OFF,
+            ExposureMode::Auto => ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_AUTO,
+            ExposureMode::Night => ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_NIGHT,
+            ExposureMode::NightPreview => {
+                ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_NIGHTPREVIEW
+            }
+            ExposureMode::Backlight => {
+                ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_BACKLIGHT
+            }
+            ExposureMode::Spotlight => {
+                ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_SPOTLIGHT
+            }
+            ExposureMode::Sports => ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_SPORTS,
+            ExposureMode::Snow => ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_SNOW,
+            ExposureMode::Beach => ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_BEACH,
+            ExposureMode::VeryLong => {
+                ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_VERYLONG
+            }
+            ExposureMode::FixedFps => {
+                ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_FIXEDFPS
+            }
+            ExposureMode::AntiShake => {
+                ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_ANTISHAKE
+            }
+            ExposureMode::Fireworks => {
+                ffi::MMAL_PARAM_EXPOSUREMODE_T_MMAL_PARAM_EXPOSUREMODE_FIREWORKS
+            }
+        }
+    }
+}
+
+impl MeteringMode {
+    fn as_mmal(&self) -> ffi::MMAL_PARAM_EXPOSUREMETERINGMODE_T {
+        match self {
+            MeteringMode::Average => {
+                ffi::MMAL_PARAM_EXPOSUREMETERINGMODE_T_MMAL_PARAM_EXPOSUREMETERINGMODE_AVERAGE
+            }
+            MeteringMode::Spot => {
+                ffi::MMAL_PARAM_EXPOSUREMETERINGMODE_T_MMAL_PARAM_EXPOSUREMETERINGMODE_SPOT
+            }
+            MeteringMode::Backlit => {
+                ffi::MMAL_PARAM_EXPOSUREMETERINGMODE_T_MMAL_PARAM_EXPOSUREMETERINGMODE_BACKLIT
+            }
+            MeteringMode::Matrix => {
+                ffi::MMAL_PARAM_EXPOSUREMETERINGMODE_T_MMAL_PARAM_EXPOSUREMETERINGMODE_MATRIX
+            }
+        }
+    }
+}
+
+impl DrcStrength {
+    fn as_mmal(&self) -> ffi::MMAL_PARAMETER_DRC_STRENGTH_T {
+        match self {
+            DrcStrength::Off => ffi::MMAL_PARAMETER_DRC_STRENGTH_T_MMAL_PARAMETER_DRC_STRENGTH_OFF,
+            DrcStrength::Low => ffi::MMAL_PARAMETER_DRC_STRENGTH_T_MMAL_PARAMETER_DRC_STRENGTH_LOW,
+            DrcStrength::Medium => {
+                ffi::MMAL_PARAMETER_DRC_STRENGTH_T_MMAL_PARAMETER_DRC_STRENGTH_MEDIUM
+            }
+            DrcStrength::High => {
+                ffi::MMAL_PARAMETER_DRC_STRENGTH_T_MMAL_PARAMETER_DRC_STRENGTH_HIGH
+            }
+        }
+    }
+}
+
+fn invalid_setting(value: &str, setting: &str) -> CaptureError {
+    CaptureError::InvalidSetting(format!("{} is not a valid {}", value, setting))
+}
+
+impl std::str::FromStr for AwbMode {
+    type Err = CaptureError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(AwbMode::Off),
+            "auto" => Ok(AwbMode::Auto),
+            "sunlight" | "sun" => Ok(AwbMode::Sunlight),
+            "cloudy" | "cloud" => Ok(AwbMode::Cloudy),
+            "shade" => Ok(AwbMode::Shade),
+            "tungsten" => Ok(AwbMode::Tungsten),
+            "fluorescent" => Ok(AwbMode::Fluorescent),
+            "incandescent" => Ok(AwbMode::Incandescent),
+            "flash" => Ok(AwbMode::Flash),
+            "horizon" => Ok(AwbMode::Horizon),
+            "greyworld" => Ok(AwbMode::Greyworld),
+            _ => Err(invalid_setting(value, "awb mode")),
+        }
+    }
+}
+
+impl std::str::FromStr for ExposureMode {
+    type Err = CaptureError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(ExposureMode::Off),
+            "auto" => Ok(ExposureMode::Auto),
+            "night" => Ok(ExposureMode::Night),
+            "nightpreview" => Ok(ExposureMode::NightPreview),
+            "backlight" => Ok(ExposureMode::Backlight),
+            "spotlight" => Ok(ExposureMode::Spotlight),
+            "sports" => Ok(ExposureMode::Sports),
+            "snow" => Ok(ExposureMode::Snow),
+            "beach" => Ok(ExposureMode::Beach),
+            "verylong" => Ok(ExposureMode::VeryLong),
+            "fixedfps" => Ok(ExposureMode::FixedFps),
+            "antishake" => Ok(ExposureMode::AntiShake),
+            "fireworks" => Ok(ExposureMode::Fireworks),
+            _ => Err(invalid_setting(value, "exposure mode")),
+        }
+    }
+}
+
+impl std::str::FromStr for MeteringMode {
+    type Err = CaptureError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "average" => Ok(MeteringMode::Average),
+            "spot" => Ok(MeteringMode::Spot),
+            "backlit" => Ok(MeteringMode::Backlit),
+            "matrix" => Ok(MeteringMode::Matrix),
+            _ => Err(invalid_setting(value, "metering mode")),
+        }
+    }
+}
+
+impl std::str::FromStr for DrcStrength {
+    type Err = CaptureError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "off" => Ok(DrcStrength::Off),
+            "low" => Ok(DrcStrength::Low),
+            "medium" | "med" => Ok(DrcStrength::Medium),
+            "high" => Ok(DrcStrength::High),
+            _ => Err(invalid_setting(value, "drc strength")),
+        }
+    }
+}
+
+/// Return the camera component.
+/// rascam doesn't expose the component, but SeriousCamera is repr(C) and the component
+/// handle is always the first field, so we can read it directly.
+unsafe fn component(camera: &SeriousCamera) -> NonNull<ffi::MMAL_COMPONENT_T> {
+    *(camera as *const SeriousCamera as *const NonNull<ffi::MMAL_COMPONENT_T>)
+}
+
+unsafe fn control_port(camera: &SeriousCamera) -> *mut ffi::MMAL_PORT_T {
+    component(camera).as_ref().control
+}
+
+/// Rotation and flips apply to the output ports rather than the control port.
+unsafe fn output_ports(camera: &SeriousCamera) -> Vec<*mut ffi::MMAL_PORT_T> {
+    let component = component(camera);
+    let component = component.as_ref();
+    (0..component.output_num as isize)
+        .map(|i| *component.output.offset(i))
+        .collect()
+}
+
+fn as_result(status: ffi::MMAL_STATUS_T::Type, context: &str) -> Result<(), CaptureError> {
+    match status {
+        ffi::MMAL_STATUS_T::MMAL_SUCCESS => Ok(()),
+        s => Err(CaptureError::CameraError(format!(
+            "{}: mmal status {}",
+            context, s
+        ))),
+    }
+}
+
+fn as_rational(value: f32) -> ffi::MMAL_RATIONAL_T {
+    ffi::MMAL_RATIONAL_T {
+        num: (value * 65536f32) as i32,
+        den: 65536,
+    }
+}
+
+/// Set the shutter speed in microseconds; 0 means automatic.
+pub fn set_shutter_speed(camera: &SeriousCamera, speed: u32) -> Result<(), CaptureError> {
+    unsafe {
+        let status = ffi::mmal_port_parameter_set_uint32(
+            control_port(camera),
+            ffi::MMAL_PARAMETER_SHUTTER_SPEED,
+            speed,
+        );
+        as_result(status, "Unable to set shutter speed")
+    }
+}
+
+pub fn set_awb_mode(camera: &SeriousCamera, mode: AwbMode) -> Result<(), CaptureError> {
+    unsafe {
+        let mut param: ffi::MMAL_PARAMETER_AWBMODE_T = mem::zeroed();
+        param.hdr.id = ffi::MMAL_PARAMETER_AWB_MODE;
+        param.hdr.size = mem::size_of::<ffi::MMAL_PARAMETER_AWBMODE_T>() as u32;
+        param.value = mode.as_mmal();
+        let status = ffi::mmal_port_parameter_set(control_port(camera), &param.hdr);
+        as_result(status, "Unable to set awb mode")
+    }
+}
+
+/// Set the red and blue gains; these only take effect when the awb mode is off.
+pub fn set_awb_gains(camera: &SeriousCamera, red: f32, blue: f32) -> Result<(), CaptureError> {
+    unsafe {
+        let mut param: ffi::MMAL_PARAMETER_AWB_GAINS_T = mem::zeroed();
+        param.hdr.id = ffi::MMAL_PARAMETER_CUSTOM_AWB_GAINS;
+        param.hdr.size = mem::size_of::<ffi::MMAL_PARAMETER_AWB_GAINS_T>() as u32;
+        param.r_gain = as_rational(red);
+        param.b_gain = as_rational(blue);
+        let status = ffi::mmal_port_parameter_set(control_port(camera), &param.hdr);
+        as_result(status, "Unable to set awb gains")
+    }
+}
+
+pub fn set_exposure_mode(camera: &SeriousCamera, mode: ExposureMode) -> Result<(), CaptureError> {
+    unsafe {
+        let mut param: ffi::MMAL_PARAMETER_EXPOSUREMODE_T = mem::zeroed();
+        param.hdr.id = ffi::MMAL_PARAMETER_EXPOSURE_MODE;
+        param.hdr.size = mem::size_of::<ffi::MMAL_PARAMETER_EXPOSUREMODE_T>() as u32;
+        param.value = mode.as_mmal();
+        let status = ffi::mmal_port_parameter_set(control_port(camera), &param.hdr);
+        as_result(status, "Unable to set exposure mode")
+    }
+}
+
+pub fn set_metering_mode(camera: &SeriousCamera, mode: MeteringMode) -> Result<(), CaptureError> {
+    unsafe {
+        let mut param: ffi::MMAL_PARAMETER_EXPOSUREMETERINGMODE_T = mem::zeroed();
+        param.hdr.id = ffi::MMAL_PARAMETER_EXP_METERING_MODE;
+        param.hdr.size = mem::size_of::<ffi::MMAL_PARAMETER_EXPOSUREMETERINGMODE_T>() as u32;
+        param.value = mode.as_mmal();
+        let status = ffi::mmal_port_parameter_set(control_port(camera), &param.hdr);
+        as_result(status, "Unable to set metering mode")
+    }
+}
+
+pub fn set_drc(camera: &SeriousCamera, strength: DrcStrength) -> Result<(), CaptureError> {
+    unsafe {
+        let mut param: ffi::MMAL_PARAMETER_DRC_T = mem::zeroed();
+        param.hdr.id = ffi::MMAL_PARAMETER_DYNAMIC_RANGE_COMPRESSION;
+        param.hdr.size = mem::size_of::<ffi::MMAL_PARAMETER_DRC_T>() as u32;
+        param.strength = strength.as_mmal();
+        let status = ffi::mmal_port_parameter_set(control_port(camera), &param.hdr);
+        as_result(status, "Unable to set drc strength")
+    }
+}
+
+pub fn set_flip(camera: &SeriousCamera, hflip: bool, vflip: bool) -> Result<(), CaptureError> {
+    let value = match (hflip, vflip) {
+        (false, false) => ffi::MMAL_PARAM_MIRROR_T_MMAL_PARAM_MIRROR_NONE,
+        (true, false) => ffi::MMAL_PARAM_MIRROR_T_MMAL_PARAM_MIRROR_HORIZONTAL,
+        (false, true) => ffi::MMAL_PARAM_MIRROR_T_MMAL_PARAM_MIRROR_VERTICAL,
+        (true, true) => ffi::MMAL_PARAM_MIRROR_T_MMAL_PARAM_MIRROR_BOTH,
+    };
+    unsafe {
+        let mut param: ffi::MMAL_PARAMETER_MIRROR_T = mem::zeroed();
+        param.hdr.id = ffi::MMAL_PARAMETER_MIRROR;
+        param.hdr.size = mem::size_of::<ffi::MMAL_PARAMETER_MIRROR_T>() as u32;
+        param.value = value;
+        for port in output_ports(camera) {
+            let status = ffi::mmal_port_parameter_set(port, &param.hdr);
+            as_result(status, "Unable to set flip")?;
+        }
+    }
+    Ok(())
+}
+
+/// Set the rotation in degrees; the camera only supports multiples of 90.
+pub fn set_rotation(camera: &SeriousCamera, degrees: i32) -> Result<(), CaptureError> {
+    unsafe {
+        for port in output_ports(camera) {
+            let status =
+                ffi::mmal_port_parameter_set_int32(port, ffi::MMAL_PARAMETER_ROTATION, degrees);
+            as_result(status, "Unable to set rotation")?;
+        }
+    }
+    Ok(())
+}
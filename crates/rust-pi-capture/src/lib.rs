@@ -1,16 +1,16 @@
+mod control;
 mod helpers;
+mod settings;
 
-use self::error::CaptureError;
+pub use self::control::{AwbMode, DrcStrength, ExposureMode, MeteringMode};
+pub use self::error::CaptureError;
+pub use self::settings::{CaptureSettings, CaptureSettingsBuilder};
 use rascam::*;
 use std::mem::size_of;
 use std::os::raw::{c_char, c_int, c_uchar};
 use std::process::exit;
 use std::ptr::{null, null_mut};
 
-pub struct CaptureSettings {
-    resolution: (u32, u32),
-}
-
 pub struct Capture {
     pub settings: CaptureSettings,
     camera: Option<SeriousCamera>,
@@ -36,16 +36,19 @@ impl Capture {
         camera.enable_control_port(true)?;
         camera.set_camera_params(&info.cameras[0])?;
 
+        // Frames are always delivered as RGB24; see get_buffer_size()
+        let (width, height) = self.settings.resolution();
         let settings = CameraSettings {
             encoding: MMAL_ENCODING_RGB24,
-            width: self.settings.resolution.0,
-            height: self.settings.resolution.1,
-            iso: ISO_AUTO,
-            zero_copy: true,
+            width,
+            height,
+            iso: self.settings.iso(),
+            zero_copy: self.settings.zero_copy(),
             use_encoder: false,
         };
 
         camera.set_camera_format(&settings)?;
+        self.settings.apply(&camera)?;
         camera.enable()?;
         camera.create_pool()?;
         camera.create_preview()?;
@@ -59,7 +62,8 @@ impl Capture {
     pub fn shutdown(self) {}
 
     pub fn get_buffer_size(&self) -> Result<usize, CaptureError> {
-        Ok((self.settings.resolution.0 * self.settings.resolution.1 * 3) as usize)
+        let (width, height) = self.settings.resolution();
+        Ok((width * height * 3) as usize)
     }
 
     pub fn read(&mut self, buffer: &mut [u8]) -> Result<(), CaptureError> {
@@ -86,9 +90,18 @@ mod error {
         NotReady,
         NoBuffer,
         NoCamerasFound,
+        InvalidSetting(String),
         CameraError(String),
     }
 
+    impl std::error::Error for CaptureError {}
+
+    impl std::fmt::Display for CaptureError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
     impl From<CameraError> for CaptureError {
         fn from(err: CameraError) -> Self {
             CaptureError::CameraError(format!("{}", err))
@@ -113,7 +126,7 @@ mod tests {
     #[test]
     fn capture_single_frame() {
         let size = (1280, 720);
-        let mut capture = Capture::new(CaptureSettings { resolution: size });
+        let mut capture = Capture::new(CaptureSettings::new(size));
 
         let buffer_size = capture.get_buffer_size().unwrap();
         let mut buffer = vec![0u8; buffer_size];
@@ -130,7 +143,7 @@ mod tests {
     #[test]
     fn capture_several_frames() {
         let size = (1280, 720);
-        let mut capture = Capture::new(CaptureSettings { resolution: size });
+        let mut capture = Capture::new(CaptureSettings::new(size));
 
         let buffer_size = capture.get_buffer_size().unwrap();
         let mut buffer = vec![0u8; buffer_size];
@@ -0,0 +1,217 @@
+use crate::control::{self, AwbMode, DrcStrength, ExposureMode, MeteringMode};
+use crate::error::CaptureError;
+use rascam::{SeriousCamera, ISO_AUTO};
+
+/// The camera settings; use CaptureSettings::builder() to create a customized set.
+/// Any value left unset is left at the camera default (ie. automatic).
+#[derive(Debug, Clone)]
+pub struct CaptureSettings {
+    resolution: (u32, u32),
+    iso: u32,
+    shutter_speed: u32,
+    exposure_mode: Option<ExposureMode>,
+    awb_mode: Option<AwbMode>,
+    awb_gains: Option<(f32, f32)>,
+    metering_mode: Option<MeteringMode>,
+    drc: Option<DrcStrength>,
+    hflip: bool,
+    vflip: bool,
+    rotation: i32,
+    zero_copy: bool,
+}
+
+pub struct CaptureSettingsBuilder {
+    settings: CaptureSettings,
+}
+
+impl CaptureSettings {
+    /// Default settings for the given resolution
+    pub fn new(resolution: (u32, u32)) -> CaptureSettings {
+        CaptureSettings {
+            resolution,
+            iso: ISO_AUTO,
+            shutter_speed: 0,
+            exposure_mode: None,
+            awb_mode: None,
+            awb_gains: None,
+            metering_mode: None,
+            drc: None,
+            hflip: false,
+            vflip: false,
+            rotation: 0,
+            zero_copy: true,
+        }
+    }
+
+    pub fn builder(resolution: (u32, u32)) -> CaptureSettingsBuilder {
+        CaptureSettingsBuilder {
+            settings: CaptureSettings::new(resolution),
+        }
+    }
+
+    pub fn resolution(&self) -> (u32, u32) {
+        self.resolution
+    }
+
+    pub fn iso(&self) -> u32 {
+        self.iso
+    }
+
+    pub fn shutter_speed(&self) -> u32 {
+        self.shutter_speed
+    }
+
+    pub fn zero_copy(&self) -> bool {
+        self.zero_copy
+    }
+
+    /// Apply the control parameters to the camera; the format (resolution, iso)
+    /// is applied separately by set_camera_format.
+    pub(crate) fn apply(&self, camera: &SeriousCamera) -> Result<(), CaptureError> {
+        control::set_shutter_speed(camera, self.shutter_speed)?;
+        if let Some(mode) = self.exposure_mode {
+            control::set_exposure_mode(camera, mode)?;
+        }
+        if let Some(mode) = self.awb_mode {
+            control::set_awb_mode(camera, mode)?;
+        }
+        if let Some((red, blue)) = self.awb_gains {
+            control::set_awb_gains(camera, red, blue)?;
+        }
+        if let Some(mode) = self.metering_mode {
+            control::set_metering_mode(camera, mode)?;
+        }
+        if let Some(strength) = self.drc {
+            control::set_drc(camera, strength)?;
+        }
+        control::set_flip(camera, self.hflip, self.vflip)?;
+        control::set_rotation(camera, self.rotation)?;
+        Ok(())
+    }
+}
+
+impl CaptureSettingsBuilder {
+    /// The sensor ISO, or 0 for auto.
+    pub fn iso(mut self, iso: u32) -> Self {
+        self.settings.iso = iso;
+        self
+    }
+
+    /// The shutter speed in microseconds, or 0 for auto.
+    pub fn shutter_speed(mut self, speed: u32) -> Self {
+        self.settings.shutter_speed = speed;
+        self
+    }
+
+    pub fn exposure_mode(mut self, mode: ExposureMode) -> Self {
+        self.settings.exposure_mode = Some(mode);
+        self
+    }
+
+    pub fn awb_mode(mut self, mode: AwbMode) -> Self {
+        self.settings.awb_mode = Some(mode);
+        self
+    }
+
+    /// Manual red and blue gains; requires the awb mode to be off.
+    pub fn awb_gains(mut self, red: f32, blue: f32) -> Self {
+        self.settings.awb_gains = Some((red, blue));
+        self
+    }
+
+    pub fn metering_mode(mut self, mode: MeteringMode) -> Self {
+        self.settings.metering_mode = Some(mode);
+        self
+    }
+
+    pub fn drc(mut self, strength: DrcStrength) -> Self {
+        self.settings.drc = Some(strength);
+        self
+    }
+
+    pub fn flip(mut self, hflip: bool, vflip: bool) -> Self {
+        self.settings.hflip = hflip;
+        self.settings.vflip = vflip;
+        self
+    }
+
+    /// Rotation in degrees; must be a multiple of 90.
+    pub fn rotation(mut self, degrees: i32) -> Self {
+        self.settings.rotation = degrees;
+        self
+    }
+
+    pub fn zero_copy(mut self, zero_copy: bool) -> Self {
+        self.settings.zero_copy = zero_copy;
+        self
+    }
+
+    pub fn build(mut self) -> Result<CaptureSettings, CaptureError> {
+        let settings = &mut self.settings;
+        if settings.resolution.0 == 0 || settings.resolution.1 == 0 {
+            return Err(CaptureError::InvalidSetting(format!(
+                "{}x{} is not a valid resolution",
+                settings.resolution.0, settings.resolution.1
+            )));
+        }
+        if settings.iso != ISO_AUTO && !(100..=3200).contains(&settings.iso) {
+            return Err(CaptureError::InvalidSetting(format!(
+                "iso {} is not in the range 100-3200",
+                settings.iso
+            )));
+        }
+        if settings.rotation % 90 != 0 {
+            return Err(CaptureError::InvalidSetting(format!(
+                "rotation {} is not a multiple of 90",
+                settings.rotation
+            )));
+        }
+        settings.rotation = settings.rotation.rem_euclid(360);
+        if let Some((red, blue)) = settings.awb_gains {
+            if settings.awb_mode.unwrap_or(AwbMode::Off) != AwbMode::Off {
+                return Err(CaptureError::InvalidSetting(
+                    "awb gains require the awb mode to be off".to_string(),
+                ));
+            }
+            if red <= 0f32 || blue <= 0f32 || red > 8f32 || blue > 8f32 {
+                return Err(CaptureError::InvalidSetting(format!(
+                    "awb gains {},{} are not in the range 0-8",
+                    red, blue
+                )));
+            }
+            settings.awb_mode = Some(AwbMode::Off);
+        }
+        Ok(self.settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AwbMode, CaptureSettings};
+
+    #[test]
+    fn build_settings() {
+        let settings = CaptureSettings::builder((1280, 720))
+            .iso(400)
+            .shutter_speed(10000)
+            .rotation(-90)
+            .build()
+            .unwrap();
+        assert_eq!(settings.iso(), 400);
+        assert_eq!(settings.shutter_speed(), 10000);
+        assert_eq!(settings.rotation, 270);
+    }
+
+    #[test]
+    fn reject_invalid_settings() {
+        assert!(CaptureSettings::builder((1280, 720))
+            .rotation(45)
+            .build()
+            .is_err());
+        assert!(CaptureSettings::builder((1280, 720))
+            .awb_mode(AwbMode::Auto)
+            .awb_gains(1.5, 1.2)
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,30 @@
+mod alignment;
+mod change_detector;
+mod frame_stats;
+mod frame_validator;
+mod perceptual_hash;
+
+pub use self::alignment::{Aligner, Alignment};
+pub use self::change_detector::{ChangeDetector, ChangeMetric, FrameSignature};
+pub use self::error::AnalysisError;
+pub use self::frame_stats::FrameStats;
+pub use self::frame_validator::{FrameValidator, Rejection, ValidationRules};
+pub use self::perceptual_hash::PerceptualHash;
+
+pub mod error {
+    use std::error::Error;
+    use std::fmt;
+
+    #[derive(Debug)]
+    pub enum AnalysisError {
+        InvalidSettings(String),
+    }
+
+    impl fmt::Display for AnalysisError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+
+    impl Error for AnalysisError {}
+}
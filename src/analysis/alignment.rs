@@ -0,0 +1,427 @@
+use crate::analysis::error::AnalysisError;
+use crate::analysis::frame_stats::luma;
+use image::imageops::FilterType;
+use image::{ImageBuffer, Rgb};
+use std::f32::consts::PI;
+use std::ops::Deref;
+
+/// How a frame is displaced from the reference frame: the reference point p appears at
+/// R(angle)(p - centre) + centre + (dx, dy) in the frame. Offsets are in frame pixels and
+/// the angle is in degrees, clockwise.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Alignment {
+    pub dx: f32,
+    pub dy: f32,
+    pub angle: f32,
+
+    /// The height of the correlation peak; near 1 for a clean match, near 0 for no match
+    pub confidence: f32,
+}
+
+impl Alignment {
+    /// The displacement from the reference to a frame aligned against this one
+    pub fn then(&self, next: &Alignment) -> Alignment {
+        let (sin, cos) = next.angle.to_radians().sin_cos();
+        Alignment {
+            dx: cos * self.dx - sin * self.dy + next.dx,
+            dy: sin * self.dx + cos * self.dy + next.dy,
+            angle: self.angle + next.angle,
+            confidence: self.confidence.min(next.confidence),
+        }
+    }
+
+    /// Where the reference point x, y is in the frame, for a frame of the given size
+    pub fn map(&self, x: f32, y: f32, width: u32, height: u32) -> (f32, f32) {
+        let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let (px, py) = (x - cx, y - cy);
+        (
+            cos * px - sin * py + cx + self.dx,
+            sin * px + cos * py + cy + self.dy,
+        )
+    }
+}
+
+/// Estimates the translation (and optionally rotation) between frames by phase correlation
+/// of downscaled luma images.
+pub struct Aligner {
+    size: usize,
+    max_rotation: f32,
+    reference: Option<Vec<Complex>>,
+}
+
+impl Aligner {
+    /// `size` is the size of the square analysis image; it is rounded up to a power of two
+    pub fn new(size: usize) -> Aligner {
+        Aligner {
+            size: size.max(16).next_power_of_two(),
+            max_rotation: 0.0,
+            reference: None,
+        }
+    }
+
+    /// Also search for rotations of up to this many degrees either way
+    pub fn with_rotation(mut self, max_degrees: f32) -> Aligner {
+        self.max_rotation = max_degrees.abs();
+        self
+    }
+
+    pub fn set_reference<C>(&mut self, image: &ImageBuffer<Rgb<u8>, C>)
+    where
+        C: Deref<Target = [u8]>,
+    {
+        let samples = Samples::from_image(image, self.size);
+        self.reference = Some(self.spectrum(&samples, 0.0));
+    }
+
+    pub fn align<C>(&self, image: &ImageBuffer<Rgb<u8>, C>) -> Result<Alignment, AnalysisError>
+    where
+        C: Deref<Target = [u8]>,
+    {
+        let reference = match self.reference.as_ref() {
+            Some(r) => r,
+            None => {
+                return Err(AnalysisError::InvalidSettings(
+                    "no reference frame to align against".to_string(),
+                ))
+            }
+        };
+        let samples = Samples::from_image(image, self.size);
+
+        let mut best = self.correlate(reference, &samples, 0.0);
+        if self.max_rotation > 0.0 {
+            // Coarse search, then refine around the best angle
+            for (step, range) in [(0.5f32, self.max_rotation), (0.1f32, 0.5f32)].iter() {
+                let centre = best.2;
+                let count = (range / step).ceil() as i32;
+                for i in -count..=count {
+                    let angle = (centre + i as f32 * step)
+                        .max(-self.max_rotation)
+                        .min(self.max_rotation);
+                    let candidate = self.correlate(reference, &samples, angle);
+                    if candidate.3 > best.3 {
+                        best = candidate;
+                    }
+                }
+            }
+        }
+
+        // The correlation finds the shift of the derotated frame; rotate it back
+        let (ex, ey, angle, confidence) = best;
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (sx, sy) = (ex / samples.scale, ey / samples.scale);
+        Ok(Alignment {
+            dx: cos * sx - sin * sy,
+            dy: sin * sx + cos * sy,
+            angle,
+            confidence,
+        })
+    }
+
+    /// Returns the shift, angle and peak height for the frame derotated by angle
+    fn correlate(
+        &self,
+        reference: &[Complex],
+        samples: &Samples,
+        angle: f32,
+    ) -> (f32, f32, f32, f32) {
+        let spectrum = self.spectrum(samples, angle);
+        let mut surface: Vec<Complex> = reference
+            .iter()
+            .zip(spectrum.iter())
+            .map(|(a, b)| {
+                let product = a.conj().mul(*b);
+                let magnitude = product.norm();
+                if magnitude > 1e-6 {
+                    product.scale(1.0 / magnitude)
+                } else {
+                    Complex::default()
+                }
+            })
+            .collect();
+        fft2d(&mut surface, self.size, true);
+
+        let (peak, _) =
+            surface
+                .iter()
+                .enumerate()
+                .fold((0, f32::MIN), |(best, value), (index, c)| {
+                    if c.re > value {
+                        (index, c.re)
+                    } else {
+                        (best, value)
+                    }
+                });
+        let n = self.size;
+        let value = |x: usize, y: usize| surface[(y % n) * n + (x % n)].re;
+        let (px, py) = (peak % n, peak / n);
+
+        // Parabolic fit around the peak for sub pixel accuracy
+        let refine = |before: f32, centre: f32, after: f32| {
+            let denominator = before - 2.0 * centre + after;
+            if denominator.abs() > 1e-6 {
+                (0.5 * (before - after) / denominator).clamp(-0.5, 0.5)
+            } else {
+                0.0
+            }
+        };
+        let fx = refine(value(px + n - 1, py), value(px, py), value(px + 1, py));
+        let fy = refine(value(px, py + n - 1), value(px, py), value(px, py + 1));
+        let wrap = |p: usize| {
+            if p >= n / 2 {
+                p as f32 - n as f32
+            } else {
+                p as f32
+            }
+        };
+        (wrap(px) + fx, wrap(py) + fy, angle, value(px, py))
+    }
+
+    /// The spectrum of the samples rotated by angle, with the mean removed and a window
+    /// applied so the edges of the frame do not dominate the correlation
+    fn spectrum(&self, samples: &Samples, angle: f32) -> Vec<Complex> {
+        let n = self.size;
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (cx, cy) = (samples.width as f32 / 2.0, samples.height as f32 / 2.0);
+        let mut data = vec![Complex::default(); n * n];
+        for y in 0..samples.height {
+            for x in 0..samples.width {
+                let (px, py) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+                let value = samples.sample(cos * px - sin * py + cx, sin * px + cos * py + cy);
+                let window = hann(x, samples.width) * hann(y, samples.height);
+                data[(y + samples.top) * n + x + samples.left].re = (value - samples.mean) * window;
+            }
+        }
+        fft2d(&mut data, n, false);
+        data
+    }
+}
+
+/// A luma image scaled to fit inside the analysis square
+struct Samples {
+    values: Vec<f32>,
+    width: usize,
+    height: usize,
+    left: usize,
+    top: usize,
+    mean: f32,
+
+    /// Analysis pixels per frame pixel
+    scale: f32,
+}
+
+impl Samples {
+    fn from_image<C>(image: &ImageBuffer<Rgb<u8>, C>, size: usize) -> Samples
+    where
+        C: Deref<Target = [u8]>,
+    {
+        let (width, height) = image.dimensions();
+        let scale = size as f32 / width.max(height).max(1) as f32;
+        let w = ((width as f32 * scale).round() as usize).max(1).min(size);
+        let h = ((height as f32 * scale).round() as usize).max(1).min(size);
+        let values: Vec<f32> =
+            image::imageops::resize(image, w as u32, h as u32, FilterType::Triangle)
+                .pixels()
+                .map(luma)
+                .collect();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        Samples {
+            values,
+            width: w,
+            height: h,
+            left: (size - w) / 2,
+            top: (size - h) / 2,
+            mean,
+            scale,
+        }
+    }
+
+    /// Bilinear sample at a pixel position; the mean outside the image
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let (x, y) = (x - 0.5, y - 0.5);
+        if x < 0.0 || y < 0.0 || x > (self.width - 1) as f32 || y > (self.height - 1) as f32 {
+            return self.mean;
+        }
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let at = |x: usize, y: usize| self.values[y * self.width + x];
+        let top = at(x0, y0) * (1.0 - fx) + at(x1, y0) * fx;
+        let bottom = at(x0, y1) * (1.0 - fx) + at(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+fn hann(i: usize, n: usize) -> f32 {
+    if n < 2 {
+        return 1.0;
+    }
+    0.5 - 0.5 * (2.0 * PI * i as f32 / (n - 1) as f32).cos()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn add(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+
+    fn scale(self, factor: f32) -> Complex {
+        Complex {
+            re: self.re * factor,
+            im: self.im * factor,
+        }
+    }
+
+    fn conj(self) -> Complex {
+        Complex {
+            re: self.re,
+            im: -self.im,
+        }
+    }
+
+    fn norm(self) -> f32 {
+        (self.re * self.re + self.im * self.im).sqrt()
+    }
+}
+
+/// In place radix 2 fft; the length must be a power of two
+fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut length = 2;
+    while length <= n {
+        let half = length / 2;
+        for k in 0..half {
+            let theta = sign * 2.0 * std::f64::consts::PI * k as f64 / length as f64;
+            let w = Complex {
+                re: theta.cos() as f32,
+                im: theta.sin() as f32,
+            };
+            for start in (0..n).step_by(length) {
+                let u = data[start + k];
+                let v = data[start + k + half].mul(w);
+                data[start + k] = u.add(v);
+                data[start + k + half] = u.sub(v);
+            }
+        }
+        length <<= 1;
+    }
+
+    if inverse {
+        for value in data.iter_mut() {
+            *value = value.scale(1.0 / n as f32);
+        }
+    }
+}
+
+/// fft of an n x n row major grid
+fn fft2d(data: &mut [Complex], n: usize, inverse: bool) {
+    for row in data.chunks_mut(n) {
+        fft(row, inverse);
+    }
+    let mut column = vec![Complex::default(); n];
+    for x in 0..n {
+        for y in 0..n {
+            column[y] = data[y * n + x];
+        }
+        fft(&mut column, inverse);
+        for y in 0..n {
+            data[y * n + x] = column[y];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aligner, Alignment};
+    use image::{Rgb, RgbImage};
+
+    /// A textured scene, displaced by dx, dy and rotated by angle degrees about the centre
+    fn scene(dx: f32, dy: f32, angle: f32) -> RgbImage {
+        let (sin, cos) = (-angle).to_radians().sin_cos();
+        RgbImage::from_fn(240, 160, |x, y| {
+            let (px, py) = (x as f32 - 120.0 - dx, y as f32 - 80.0 - dy);
+            let (rx, ry) = (cos * px - sin * py + 120.0, sin * px + cos * py + 80.0);
+            let blob = |cx: f32, cy: f32, r: f32| (rx - cx).powi(2) + (ry - cy).powi(2) < r * r;
+            let mut v = ((rx * 0.11).sin() * (ry * 0.07).cos() * 60.0 + 100.0) as u8;
+            if blob(70.0, 60.0, 18.0) || blob(170.0, 110.0, 12.0) {
+                v = 240;
+            }
+            if rx > 150.0 && rx < 200.0 && ry > 30.0 && ry < 50.0 {
+                v = 20;
+            }
+            Rgb([v, v, v])
+        })
+    }
+
+    #[test]
+    pub fn test_finds_translation() {
+        let mut aligner = Aligner::new(128);
+        aligner.set_reference(&scene(0.0, 0.0, 0.0));
+        let alignment = aligner.align(&scene(9.0, -6.0, 0.0)).unwrap();
+        assert!((alignment.dx - 9.0).abs() < 1.5, "{:?}", alignment);
+        assert!((alignment.dy + 6.0).abs() < 1.5, "{:?}", alignment);
+        assert!(alignment.confidence > 0.2);
+    }
+
+    #[test]
+    pub fn test_finds_rotation() {
+        let mut aligner = Aligner::new(128).with_rotation(3.0);
+        aligner.set_reference(&scene(0.0, 0.0, 0.0));
+        let alignment = aligner.align(&scene(4.0, 0.0, 2.0)).unwrap();
+        assert!((alignment.angle - 2.0).abs() < 0.5, "{:?}", alignment);
+        assert!((alignment.dx - 4.0).abs() < 2.0, "{:?}", alignment);
+    }
+
+    #[test]
+    pub fn test_compose() {
+        let a = Alignment {
+            dx: 3.0,
+            dy: 1.0,
+            ..Default::default()
+        };
+        let b = Alignment {
+            dx: -1.0,
+            dy: 2.0,
+            ..Default::default()
+        };
+        let total = a.then(&b);
+        assert_eq!((total.dx, total.dy), (2.0, 3.0));
+        assert_eq!(total.map(10.0, 10.0, 20, 20), (12.0, 13.0));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::analysis::error::AnalysisError;
+use crate::analysis::frame_stats::luma;
+use crate::analysis::perceptual_hash::{luma_thumbnail, PerceptualHash};
+use image::{ImageBuffer, Rgb};
+use std::ops::Deref;
+use std::str::FromStr;
+
+const THUMBNAIL_WIDTH: u32 = 64;
+const THUMBNAIL_HEIGHT: u32 = 48;
+const HISTOGRAM_BINS: usize = 64;
+
+/// How the difference between two frames is measured
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeMetric {
+    /// Mean absolute luminance difference of a downscaled frame
+    Pixel,
+    /// Distance between the luminance histograms
+    Histogram,
+    /// Hamming distance between perceptual hashes
+    PerceptualHash,
+}
+
+impl FromStr for ChangeMetric {
+    type Err = AnalysisError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pixel" => Ok(ChangeMetric::Pixel),
+            "histogram" => Ok(ChangeMetric::Histogram),
+            "phash" => Ok(ChangeMetric::PerceptualHash),
+            _ => Err(AnalysisError::InvalidSettings(format!(
+                "{} is not a valid change metric; use pixel, histogram or phash",
+                s
+            ))),
+        }
+    }
+}
+
+/// The part of a frame the metric needs, so the last frame doesn't have to be kept.
+#[derive(Debug, Clone)]
+pub enum FrameSignature {
+    Pixels(Vec<f32>),
+    Histogram(Vec<f32>),
+    Hash(PerceptualHash),
+}
+
+impl FrameSignature {
+    pub fn from_image<C>(metric: ChangeMetric, image: &ImageBuffer<Rgb<u8>, C>) -> FrameSignature
+    where
+        C: Deref<Target = [u8]>,
+    {
+        match metric {
+            ChangeMetric::Pixel => {
+                FrameSignature::Pixels(luma_thumbnail(image, THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT))
+            }
+            ChangeMetric::Histogram => {
+                let mut histogram = vec![0f32; HISTOGRAM_BINS];
+                for pixel in image.pixels() {
+                    let bin = (luma(pixel) as usize * HISTOGRAM_BINS / 256).min(HISTOGRAM_BINS - 1);
+                    histogram[bin] += 1.0;
+                }
+                let total = (image.width() * image.height()).max(1) as f32;
+                FrameSignature::Histogram(histogram.iter().map(|v| v / total).collect())
+            }
+            ChangeMetric::PerceptualHash => FrameSignature::Hash(PerceptualHash::from_image(image)),
+        }
+    }
+
+    /// How different the frames are, from 0 (the same) to 1 (completely different)
+    pub fn difference(&self, other: &FrameSignature) -> f32 {
+        match (self, other) {
+            (FrameSignature::Pixels(a), FrameSignature::Pixels(b)) => {
+                let total: f32 = a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).sum();
+                total / (a.len().max(1) as f32 * 255.0)
+            }
+            (FrameSignature::Histogram(a), FrameSignature::Histogram(b)) => {
+                let total: f32 = a.iter().zip(b.iter()).map(|(x, y)| (x - y).abs()).sum();
+                total / 2.0
+            }
+            (FrameSignature::Hash(a), FrameSignature::Hash(b)) => a.distance(b) as f32 / 64.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Decides whether a frame differs enough from the last accepted frame to be worth keeping.
+pub struct ChangeDetector {
+    metric: ChangeMetric,
+    threshold: f32,
+    reference: Option<FrameSignature>,
+    min_interval: u64,
+    max_interval: u64,
+}
+
+impl ChangeDetector {
+    pub fn new(metric: ChangeMetric, threshold: f32) -> ChangeDetector {
+        ChangeDetector {
+            metric,
+            threshold,
+            reference: None,
+            min_interval: 0,
+            max_interval: 0,
+        }
+    }
+
+    /// Never keep frames less than min_interval ms apart, and keep a frame at least every
+    /// max_interval ms even if nothing changed; 0 disables either bound
+    pub fn with_intervals(mut self, min_interval: u64, max_interval: u64) -> ChangeDetector {
+        self.min_interval = min_interval;
+        self.max_interval = max_interval;
+        self
+    }
+
+    /// Whether to keep a frame, given if it changed and the ms since the last kept frame
+    pub fn should_save(&self, changed: bool, since_saved: Option<u128>) -> bool {
+        let too_soon = since_saved
+            .map(|t| t < self.min_interval as u128)
+            .unwrap_or(false);
+        let overdue = self.max_interval > 0
+            && since_saved
+                .map(|t| t >= self.max_interval as u128)
+                .unwrap_or(true);
+        !too_soon && (changed || overdue)
+    }
+
+    /// Compare the frame to the reference; the first frame is always a change.
+    /// Returns the signature of the frame, to pass to accept() if the frame is kept.
+    pub fn compare<C>(&self, image: &ImageBuffer<Rgb<u8>, C>) -> (bool, f32, FrameSignature)
+    where
+        C: Deref<Target = [u8]>,
+    {
+        let signature = FrameSignature::from_image(self.metric, image);
+        let difference = match self.reference.as_ref() {
+            Some(reference) => reference.difference(&signature),
+            None => 1.0,
+        };
+        (difference > self.threshold, difference, signature)
+    }
+
+    /// Make this the frame later frames are compared to
+    pub fn accept(&mut self, signature: FrameSignature) {
+        self.reference = Some(signature);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChangeDetector, ChangeMetric};
+    use image::{Rgb, RgbImage};
+
+    fn scene(square: u32) -> RgbImage {
+        RgbImage::from_fn(128, 96, |x, y| {
+            if (square..square + 40).contains(&x) && (20..60).contains(&y) {
+                Rgb([240, 240, 240])
+            } else {
+                Rgb([20, 30, (y / 2) as u8])
+            }
+        })
+    }
+
+    #[test]
+    pub fn test_detect_change() {
+        for metric in [
+            ChangeMetric::Pixel,
+            ChangeMetric::Histogram,
+            ChangeMetric::PerceptualHash,
+        ]
+        .iter()
+        {
+            let mut detector = ChangeDetector::new(*metric, 0.02);
+            let (changed, _, signature) = detector.compare(&scene(10));
+            assert!(changed);
+            detector.accept(signature);
+
+            let (changed, _, _) = detector.compare(&scene(10));
+            assert!(!changed, "{:?} detected a change in the same frame", metric);
+        }
+
+        let mut detector = ChangeDetector::new(ChangeMetric::Pixel, 0.02);
+        let (_, _, signature) = detector.compare(&scene(10));
+        detector.accept(signature);
+        let (changed, difference, _) = detector.compare(&scene(70));
+        assert!(changed, "difference was {}", difference);
+    }
+
+    #[test]
+    pub fn test_interval_bounds() {
+        let detector = ChangeDetector::new(ChangeMetric::Pixel, 0.02).with_intervals(1000, 60000);
+        assert!(detector.should_save(true, None));
+        assert!(detector.should_save(true, Some(1000)));
+
+        // Too soon
+        assert!(!detector.should_save(true, Some(999)));
+
+        // Overdue
+        assert!(!detector.should_save(false, Some(59999)));
+        assert!(detector.should_save(false, Some(60000)));
+
+        let unbounded = ChangeDetector::new(ChangeMetric::Pixel, 0.02);
+        assert!(unbounded.should_save(true, Some(0)));
+        assert!(!unbounded.should_save(false, Some(u64::MAX as u128)));
+    }
+}
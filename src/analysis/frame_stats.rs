@@ -0,0 +1,119 @@
+use image::{ImageBuffer, Rgb};
+use std::ops::Deref;
+
+/// Summary statistics of a frame, used to spot frames that are black, blank or broken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameStats {
+    /// Mean luminance, 0 to 255
+    pub mean_luma: f32,
+
+    /// Variance of the luminance
+    pub variance: f32,
+
+    /// The fraction of pixels which are strongly green, as produced by an empty yuv buffer
+    pub green_ratio: f32,
+
+    /// The fraction of rows at the bottom of the frame which are a single flat color,
+    /// as produced by a partially decoded frame
+    pub flat_tail: f32,
+}
+
+impl FrameStats {
+    pub fn from_image<C>(image: &ImageBuffer<Rgb<u8>, C>) -> FrameStats
+    where
+        C: Deref<Target = [u8]>,
+    {
+        let (width, height) = image.dimensions();
+        let count = (width as f64) * (height as f64);
+        if count == 0.0 {
+            return FrameStats {
+                mean_luma: 0.0,
+                variance: 0.0,
+                green_ratio: 0.0,
+                flat_tail: 0.0,
+            };
+        }
+
+        let mut sum = 0f64;
+        let mut sum_squares = 0f64;
+        let mut green = 0u64;
+        for pixel in image.pixels() {
+            let value = luma(pixel) as f64;
+            sum += value;
+            sum_squares += value * value;
+            if is_green(pixel) {
+                green += 1;
+            }
+        }
+        let mean = sum / count;
+
+        FrameStats {
+            mean_luma: mean as f32,
+            variance: (sum_squares / count - mean * mean).max(0.0) as f32,
+            green_ratio: (green as f64 / count) as f32,
+            flat_tail: flat_tail_rows(image) as f32 / height as f32,
+        }
+    }
+}
+
+/// Rec. 601 luminance of a pixel
+pub(crate) fn luma(pixel: &Rgb<u8>) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+fn is_green(pixel: &Rgb<u8>) -> bool {
+    let (r, g, b) = (pixel[0] as u32, pixel[1] as u32, pixel[2] as u32);
+    g > 64 && g > 2 * r.max(b) + 16
+}
+
+/// Count the rows from the bottom which are all the same color as the bottom right pixel
+fn flat_tail_rows<C>(image: &ImageBuffer<Rgb<u8>, C>) -> u32
+where
+    C: Deref<Target = [u8]>,
+{
+    let (width, height) = image.dimensions();
+    let fill = image.get_pixel(width - 1, height - 1);
+    let mut rows = 0;
+    for y in (0..height).rev() {
+        let flat = (0..width).all(|x| {
+            let pixel = image.get_pixel(x, y);
+            (0..3).all(|c| (pixel[c] as i32 - fill[c] as i32).abs() <= 2)
+        });
+        if !flat {
+            break;
+        }
+        rows += 1;
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrameStats;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    pub fn test_black_frame() {
+        let stats = FrameStats::from_image(&RgbImage::new(32, 32));
+        assert_eq!(stats.mean_luma, 0.0);
+        assert_eq!(stats.variance, 0.0);
+        assert_eq!(stats.flat_tail, 1.0);
+    }
+
+    #[test]
+    pub fn test_green_and_partial_frames() {
+        let green = RgbImage::from_pixel(32, 32, Rgb([0, 135, 0]));
+        assert_eq!(FrameStats::from_image(&green).green_ratio, 1.0);
+
+        let partial = RgbImage::from_fn(32, 32, |x, y| {
+            if y < 24 {
+                Rgb([(x * 8) as u8, (y * 8) as u8, 100])
+            } else {
+                Rgb([128, 128, 128])
+            }
+        });
+        let stats = FrameStats::from_image(&partial);
+        assert_eq!(stats.flat_tail, 0.25);
+        assert!(stats.variance > 100.0);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::analysis::FrameStats;
+use image::{ImageBuffer, Rgb};
+use std::fmt;
+use std::ops::Deref;
+
+/// Why a frame was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// The frame is too dark to be useful
+    Black,
+    /// The frame is a single flat color
+    Blank,
+    /// Most of the frame is the green of an empty yuv buffer
+    Green,
+    /// The bottom of the frame is missing, as if decoding stopped part way
+    Partial,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            Rejection::Black => "black",
+            Rejection::Blank => "blank",
+            Rejection::Green => "green",
+            Rejection::Partial => "partial",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationRules {
+    /// Frames with a mean luminance (0-255) below this are black
+    pub min_luminance: f32,
+
+    /// Frames with a luminance variance below this are blank
+    pub min_variance: f32,
+
+    /// Frames with more than this fraction of green pixels are green
+    pub max_green_ratio: f32,
+
+    /// Frames with more than this fraction of flat rows at the bottom are partial
+    pub max_flat_tail: f32,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        ValidationRules {
+            min_luminance: 8.0,
+            min_variance: 4.0,
+            max_green_ratio: 0.9,
+            max_flat_tail: 0.25,
+        }
+    }
+}
+
+pub struct FrameValidator {
+    rules: ValidationRules,
+}
+
+impl FrameValidator {
+    pub fn new(rules: ValidationRules) -> FrameValidator {
+        FrameValidator { rules }
+    }
+
+    pub fn validate<C>(&self, image: &ImageBuffer<Rgb<u8>, C>) -> Result<FrameStats, Rejection>
+    where
+        C: Deref<Target = [u8]>,
+    {
+        let stats = FrameStats::from_image(image);
+        self.check(&stats)?;
+        Ok(stats)
+    }
+
+    pub fn check(&self, stats: &FrameStats) -> Result<(), Rejection> {
+        if stats.mean_luma < self.rules.min_luminance {
+            return Err(Rejection::Black);
+        }
+        if stats.variance < self.rules.min_variance {
+            return Err(Rejection::Blank);
+        }
+        if stats.green_ratio > self.rules.max_green_ratio {
+            return Err(Rejection::Green);
+        }
+        if stats.flat_tail > self.rules.max_flat_tail {
+            return Err(Rejection::Partial);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameValidator, Rejection};
+    use image::{Rgb, RgbImage};
+
+    fn gradient(height: u32) -> RgbImage {
+        RgbImage::from_fn(64, 64, |x, y| {
+            if y < height {
+                Rgb([(x * 4) as u8, (y * 4) as u8, 64])
+            } else {
+                Rgb([128, 128, 128])
+            }
+        })
+    }
+
+    #[test]
+    pub fn test_validate_frames() {
+        let validator = FrameValidator::new(Default::default());
+        assert!(validator.validate(&gradient(64)).is_ok());
+        assert_eq!(
+            validator.validate(&RgbImage::new(64, 64)),
+            Err(Rejection::Black)
+        );
+        assert_eq!(
+            validator.validate(&RgbImage::from_pixel(64, 64, Rgb([90, 90, 90]))),
+            Err(Rejection::Blank)
+        );
+        assert_eq!(validator.validate(&gradient(32)), Err(Rejection::Partial));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::analysis::frame_stats::luma;
+use image::imageops::FilterType;
+use image::{ImageBuffer, Rgb};
+use std::f32::consts::PI;
+use std::ops::Deref;
+
+const SIZE: usize = 32;
+const BITS: usize = 8;
+
+/// A 64 bit DCT based perceptual hash; similar images have hashes a small hamming distance apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PerceptualHash(pub u64);
+
+impl PerceptualHash {
+    pub fn from_image<C>(image: &ImageBuffer<Rgb<u8>, C>) -> PerceptualHash
+    where
+        C: Deref<Target = [u8]>,
+    {
+        let pixels = luma_thumbnail(image, SIZE as u32, SIZE as u32);
+
+        // Only the low frequency corner of the dct is needed
+        let mut coefficients = [0f32; BITS * BITS];
+        for v in 0..BITS {
+            for u in 0..BITS {
+                let mut sum = 0f32;
+                for y in 0..SIZE {
+                    let cy = ((2 * y + 1) as f32 * v as f32 * PI / (2 * SIZE) as f32).cos();
+                    for x in 0..SIZE {
+                        let cx = ((2 * x + 1) as f32 * u as f32 * PI / (2 * SIZE) as f32).cos();
+                        sum += pixels[y * SIZE + x] * cx * cy;
+                    }
+                }
+                coefficients[v * BITS + u] = sum;
+            }
+        }
+
+        // Skip the dc term, which is just the average brightness
+        let mut sorted = coefficients[1..].to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let median = sorted[sorted.len() / 2];
+
+        let mut hash = 0u64;
+        for (bit, value) in coefficients.iter().enumerate() {
+            if *value > median {
+                hash |= 1 << bit;
+            }
+        }
+        PerceptualHash(hash)
+    }
+
+    /// The number of bits which differ, from 0 (identical) to 64
+    pub fn distance(&self, other: &PerceptualHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+/// Downscale the image and return its luminance, row by row
+pub(crate) fn luma_thumbnail<C>(
+    image: &ImageBuffer<Rgb<u8>, C>,
+    width: u32,
+    height: u32,
+) -> Vec<f32>
+where
+    C: Deref<Target = [u8]>,
+{
+    image::imageops::resize(image, width, height, FilterType::Triangle)
+        .pixels()
+        .map(luma)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PerceptualHash;
+    use image::{Rgb, RgbImage};
+
+    fn scene(offset: i64, noise: u8) -> RgbImage {
+        RgbImage::from_fn(128, 96, |x, y| {
+            let (dx, dy) = (x as i64 - 32 - offset, y as i64 - 48);
+            let base = (x + y) as u8;
+            let v = if dx * dx + dy * dy < 400 { 230 } else { base };
+            Rgb([v.saturating_add((x * y % 3) as u8 * noise), v, v])
+        })
+    }
+
+    #[test]
+    pub fn test_similar_images_have_similar_hashes() {
+        let original = PerceptualHash::from_image(&scene(0, 0));
+        let noisy = PerceptualHash::from_image(&scene(0, 2));
+        let moved = PerceptualHash::from_image(&scene(64, 0));
+        assert!(original.distance(&noisy) <= 4);
+        assert!(original.distance(&moved) > 16);
+    }
+}
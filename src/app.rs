@@ -1,29 +1,65 @@
+mod capture_status;
 pub mod config;
+mod frame_recovery;
 mod image_logger;
+mod run_state;
+mod status_server;
 
 use self::config::Manifest;
 use self::error::AppError;
-use crate::hardware::CameraFactory;
-use crate::resources::{ConfigMap, LockFile, ResourceFolder, TimeProbe, TimeProbeConfig};
+use crate::analysis::{ChangeDetector, ChangeMetric, FrameValidator, ValidationRules};
+use crate::encoding::{parse_color, Overlay, OverlayPosition, RollingPreview};
+use crate::hardware::{CameraFactory, CameraLike, CameraRegistry};
+use crate::resources::{
+    shutdown_requested, CaptureControl, ConfigMap, FolderLayout, LockFile, LowSpaceAction,
+    ResourceFolder, Retention, RetentionError, RetentionPolicy, TimeProbe, TimeProbeConfig,
+    TimeSnapshot,
+};
+use chrono::Utc;
 use slog::o;
-use slog::{info, Drain, Duplicate, Logger};
+use slog::{debug, error, info, warn, Drain, Duplicate, Logger};
 use sloggers::file::FileLoggerBuilder;
 use sloggers::terminal::{Destination, TerminalLoggerBuilder};
 use sloggers::types::Severity;
 use sloggers::Build;
 
+use crate::app::capture_status::{CaptureStatus, SharedStatus};
+use crate::app::frame_recovery::quarantine_incomplete;
 use crate::app::image_logger::ImageLogger;
+use crate::app::run_state::RunStateFile;
+use crate::app::status_server::StatusServer;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Instant;
 
+/// What the capture loop keeps from one sample to the next
+struct CaptureState {
+    image_logger: ImageLogger,
+    validator: Option<FrameValidator>,
+    detector: Option<ChangeDetector>,
+    preview: Option<RollingPreview>,
+    run_state: RunStateFile,
+
+    /// When the last frame was saved in change triggered mode, in ms since the start
+    last_saved: Option<u128>,
+}
+
 pub struct App {
     manifest: Manifest,
     output: ResourceFolder,
     logger: Logger,
-    camera_config: ConfigMap,
+    camera_factory: CameraFactory,
+    status: SharedStatus,
 }
 
 impl App {
     pub fn new(manifest: Manifest) -> Result<App, AppError> {
+        App::with_registry(manifest, Default::default())
+    }
+
+    /// Create an app which selects its camera from the given set of backends
+    pub fn with_registry(manifest: Manifest, registry: CameraRegistry) -> Result<App, AppError> {
         let output_folder = ResourceFolder::new(&manifest.config.output_folder).require()?;
         let log_folder = ResourceFolder::new(&manifest.config.log_folder).require()?;
         let config = App::create_camera_config(&manifest);
@@ -31,7 +67,8 @@ impl App {
             manifest,
             output: output_folder,
             logger: App::create_logger(log_folder)?,
-            camera_config: config,
+            camera_factory: CameraFactory::with_registry(config, registry),
+            status: CaptureStatus::shared(),
         })
     }
 
@@ -56,23 +93,344 @@ impl App {
         Ok(logger)
     }
 
-    pub fn run(&mut self) -> Result<(), AppError> {
-        // Setup a camera based on the manifest
-        let camera_factory = CameraFactory::new(self.camera_config.clone());
-        let mut camera = camera_factory.create_camera()?;
+    fn create_image_logger(&self) -> Result<ImageLogger, AppError> {
+        let layout = FolderLayout::from_str(&self.manifest.config.folder_layout)?;
+        let mut image_logger =
+            ImageLogger::new(self.output.clone(), self.logger.clone()).with_layout(layout);
+        if self.manifest.overlay.overlay_capture {
+            image_logger = image_logger.with_overlay(App::create_overlay(&self.manifest)?);
+        }
+        if !self.manifest.validation.validate_frames {
+            return Ok(image_logger);
+        }
+        let rejected_folder = match self.manifest.validation.rejected_folder.as_ref() {
+            Some(folder) => ResourceFolder::new(folder),
+            None => ResourceFolder::new(&self.output.path("rejected")?.to_string_lossy()),
+        };
+        Ok(image_logger.with_rejected_folder(rejected_folder.require()?))
+    }
+
+    /// The overlay described by the manifest, for drawing on frames at capture or export time
+    pub fn create_overlay(manifest: &Manifest) -> Result<Overlay, AppError> {
+        let settings = &manifest.overlay;
+        let camera = match settings.camera_name.as_ref() {
+            Some(name) => name.clone(),
+            None => manifest
+                .settings
+                .get("backend")
+                .cloned()
+                .unwrap_or_default(),
+        };
+        let background = if settings.background.trim().is_empty() {
+            None
+        } else {
+            Some(parse_color(&settings.background)?)
+        };
+        Ok(Overlay::new(&settings.text)
+            .with_camera(&camera)
+            .with_position(OverlayPosition::from_str(&settings.position)?)
+            .with_scale(settings.scale)
+            .with_color(parse_color(&settings.color)?)
+            .with_background(background))
+    }
+
+    fn create_validator(&self) -> Option<FrameValidator> {
+        let validation = &self.manifest.validation;
+        if !validation.validate_frames {
+            return None;
+        }
+        Some(FrameValidator::new(ValidationRules {
+            min_luminance: validation.min_luminance,
+            min_variance: validation.min_variance,
+            max_green_ratio: validation.max_green_ratio,
+            max_flat_tail: validation.max_flat_tail,
+        }))
+    }
+
+    /// The rolling preview, started from the frames already in the output folder
+    fn create_preview(&self) -> Result<Option<RollingPreview>, AppError> {
+        let settings = &self.manifest.preview;
+        if !settings.live_preview {
+            return Ok(None);
+        }
+        let path = match settings.preview_file.as_ref() {
+            Some(path) => PathBuf::from(path),
+            None => self.output.path("preview.gif")?,
+        };
+        let mut preview =
+            RollingPreview::new(&path, settings.preview_frames, settings.preview_width)
+                .with_delay(settings.preview_delay);
+
+        // Only the newest frames are needed, so walk back from the newest
+        let mut existing: Vec<PathBuf> = self
+            .output
+            .walk_newest()?
+            .filter(|path| match path {
+                Ok(path) => path.extension() == Some(OsStr::new("png")),
+                Err(_) => true,
+            })
+            .take(settings.preview_frames)
+            .collect::<Result<_, _>>()?;
+        existing.reverse();
+        if let Err(err) = preview.seed(&existing) {
+            warn!(
+                self.logger,
+                "failed to load existing frames for preview: {}", err
+            );
+        }
+        Ok(Some(preview))
+    }
+
+    fn start_status_server(
+        &self,
+        control: &CaptureControl,
+    ) -> Result<Option<StatusServer>, AppError> {
+        let settings = &self.manifest.server;
+        if !settings.http_server && !settings.remote_control {
+            return Ok(None);
+        }
+        let control = if settings.remote_control {
+            Some(control.clone())
+        } else {
+            None
+        };
+        let server = StatusServer::start(
+            &settings.bind,
+            self.status.clone(),
+            self.output.clone(),
+            settings.gallery_page_size,
+            control,
+            settings.control_token.clone(),
+        )?;
+        info!(
+            self.logger,
+            "status server listening on port {}",
+            server.port()
+        );
+        Ok(Some(server))
+    }
+
+    /// Record a problem for the status server
+    fn report_error(&self, error: String) {
+        if let Ok(mut status) = self.status.lock() {
+            status.last_error = Some(error);
+        }
+    }
+
+    /// Move frames left incomplete by a crash out of the output folder
+    fn quarantine_incomplete_frames(&self) -> Result<(), AppError> {
+        let quarantine = match self.manifest.config.quarantine_folder.as_ref() {
+            Some(folder) => ResourceFolder::new(folder),
+            None => ResourceFolder::new(&self.output.path("quarantine")?.to_string_lossy()),
+        };
+        let moved = quarantine_incomplete(&self.output, &quarantine.require()?)?;
+        for path in moved.iter() {
+            warn!(self.logger, "quarantined incomplete frame: {:?}", path);
+        }
+        Ok(())
+    }
+
+    fn create_retention(&self) -> Result<Option<Retention>, AppError> {
+        let settings = &self.manifest.retention;
+        if settings.max_total_mb == 0
+            && settings.max_age_days == 0
+            && settings.thin_after_days == 0
+            && settings.min_free_mb == 0
+        {
+            return Ok(None);
+        }
+        Ok(Some(Retention::new(RetentionPolicy {
+            max_total_bytes: settings.max_total_mb * 1024 * 1024,
+            max_age_days: settings.max_age_days,
+            thin_after_days: settings.thin_after_days,
+            thin_interval: settings.thin_interval,
+            min_free_bytes: settings.min_free_mb * 1024 * 1024,
+            low_space: LowSpaceAction::from_str(&settings.low_space)?,
+        })))
+    }
 
-        // Keep running as long as the log lasts
-        let run_lock = LockFile::new(&self.manifest.config.lock_file);
+    /// Apply the retention limits; only running out of space is fatal
+    fn apply_retention(&self, retention: &Retention, now: u128) -> Result<(), AppError> {
+        match retention.apply(&self.output, now) {
+            Ok(report) => {
+                if !report.removed.is_empty() {
+                    info!(
+                        self.logger,
+                        "retention removed {} frames, freeing {} MB",
+                        report.removed.len(),
+                        report.freed / 1024 / 1024
+                    );
+                }
+                Ok(())
+            }
+            Err(err @ RetentionError::OutOfSpace { .. }) => {
+                error!(self.logger, "{}; halting capture", err);
+                self.report_error(format!("{}", err));
+                Err(err.into())
+            }
+            Err(err) => {
+                warn!(self.logger, "failed to apply retention: {}", err);
+                self.report_error(format!("failed to apply retention: {}", err));
+                Ok(())
+            }
+        }
+    }
+
+    fn open_run_state(&self, probe: &TimeProbe) -> Result<RunStateFile, AppError> {
+        let path = match self.manifest.config.run_state_file.as_ref() {
+            Some(path) => PathBuf::from(path),
+            None => self.output.path("session.toml")?,
+        };
+        let now = probe.reference_time().timestamp_millis() as u64;
+        let run_state = RunStateFile::open(&path, now, self.manifest.config.resume)?;
+        let state = run_state.state();
+        let started = TimeSnapshot::from_timestamp(state.started as u128).utc;
+        if run_state.is_resumed() {
+            info!(
+                self.logger,
+                "resuming session started {} with {} frames",
+                started.to_rfc2822(),
+                state.sequence
+            );
+        }
+        if let Ok(mut status) = self.status.lock() {
+            status.started = started;
+            status.frame_count = state.sequence;
+        }
+        Ok(run_state)
+    }
+
+    fn create_change_detector(&self) -> Result<Option<ChangeDetector>, AppError> {
+        let motion = &self.manifest.motion;
+        if !motion.capture_on_change {
+            return Ok(None);
+        }
+        let metric = ChangeMetric::from_str(&motion.change_metric)?;
+        Ok(Some(
+            ChangeDetector::new(metric, motion.change_threshold)
+                .with_intervals(motion.min_interval, motion.max_interval),
+        ))
+    }
+
+    /// Take, check and save the picture for one sample, retrying rejected frames
+    fn capture_sample(
+        &self,
+        camera: &mut dyn CameraLike,
+        state: &mut CaptureState,
+        sample: &TimeSnapshot,
+    ) -> Result<(), AppError> {
+        info!(self.logger, "snapshot start: {}", sample.utc.to_rfc2822());
+        let sample_start = Instant::now();
+
+        let mut attempt = 0;
+        loop {
+            // Take a picture
+            let frame = camera.next()?;
+            let sample_end = Instant::now();
+            let elapsed = (sample_end - sample_start).as_millis();
+            info!(
+                self.logger,
+                "captured: {}x{} image in {}ms",
+                frame.width(),
+                frame.height(),
+                elapsed
+            );
+
+            // Check the picture is usable
+            if let Some(validator) = state.validator.as_ref() {
+                if let Err(reason) = validator.validate(&frame) {
+                    warn!(
+                        self.logger,
+                        "rejected {} frame (attempt {})",
+                        reason,
+                        attempt + 1
+                    );
+                    self.report_error(format!("rejected {} frame", reason));
+                    if let Err(err) = state.image_logger.reject(frame, sample, attempt, &reason) {
+                        warn!(self.logger, "failed to save rejected frame: {:?}", err);
+                        self.report_error(format!("failed to save rejected frame: {:?}", err));
+                    }
+                    attempt += 1;
+                    if attempt > self.manifest.validation.retries {
+                        warn!(self.logger, "no valid frame after {} attempts", attempt);
+                        return Ok(());
+                    }
+                    continue;
+                }
+            }
+
+            // In change triggered mode, only keep the picture if the scene changed
+            if let Some(detector) = state.detector.as_mut() {
+                let since_saved = state.last_saved.map(|t| sample.elapsed - t);
+                let (changed, difference, signature) = detector.compare(&frame);
+                if !detector.should_save(changed, since_saved) {
+                    debug!(self.logger, "skipped frame; difference {:.4}", difference);
+                    return Ok(());
+                }
+                info!(self.logger, "scene changed; difference {:.4}", difference);
+                detector.accept(signature);
+                state.last_saved = Some(sample.elapsed);
+            }
+
+            // Save the picture
+            if let Some(preview) = state.preview.as_mut() {
+                preview.push(&frame);
+            }
+            let saved = state.image_logger.save(frame, sample)?;
+            let sequence = state.run_state.record_capture(sample.timestamp as u64)?;
+            if let Ok(mut status) = self.status.lock() {
+                status.frame_count = sequence;
+                status.last_frame = Some(saved);
+                status.last_frame_time = Some(sample.utc);
+            }
+            if let Some(preview) = state.preview.as_ref() {
+                if let Err(err) = preview.write() {
+                    warn!(self.logger, "failed to update preview: {}", err);
+                    self.report_error(format!("failed to update preview: {}", err));
+                }
+            }
+            let sample_end = Instant::now();
+            let elapsed = (sample_end - sample_start).as_millis();
+            info!(self.logger, "wrote image {} in {}ms", sequence, elapsed);
+            return Ok(());
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), AppError> {
+        // Keep running as long as the lock lasts, or until asked to stop
+        let control = CaptureControl::new().with_signals();
+        let mut run_lock = LockFile::new(&self.manifest.config.lock_file);
         run_lock.lock()?;
+        if let Some(stale) = run_lock.stale_owner() {
+            warn!(
+                self.logger,
+                "took over stale lock from process {}", stale.pid
+            );
+        }
+
+        self.quarantine_incomplete_frames()?;
+        let retention = self.create_retention()?;
+        if let Some(retention) = retention.as_ref() {
+            self.apply_retention(retention, Utc::now().timestamp_millis() as u128)?;
+        }
 
         // Setup a probe based on the manifest
+        let interval = if self.manifest.motion.capture_on_change {
+            self.manifest.motion.poll_interval
+        } else {
+            self.manifest.config.sample_interval
+        };
         let mut probe = TimeProbe::new(TimeProbeConfig {
             time_scale: self.manifest.config.time_scale,
-            interval: self.manifest.config.sample_interval,
+            interval,
             idle: self.manifest.config.sample_idle,
             samples: -1,
             lock: Some(run_lock),
+            control: Some(control.clone()),
         });
+        if let Ok(mut status) = self.status.lock() {
+            status.interval = interval;
+        }
 
         if self.manifest.config.use_ntp {
             probe.sync_network_time("pool.ntp.org")?;
@@ -83,52 +441,93 @@ impl App {
             )
         }
 
+        // Carry on the session from the last run, if there was one
+        let run_state = self.open_run_state(&probe)?;
+        probe.resume(
+            run_state.state().started as u128,
+            run_state.state().last_capture.map(|t| t as u128),
+        );
+
         // Setup an output handler from the manifest
-        let image_logger = ImageLogger::new(self.output.clone(), self.logger.clone());
+        let mut state = CaptureState {
+            image_logger: self.create_image_logger()?,
+            validator: self.create_validator(),
+            detector: self.create_change_detector()?,
+            preview: self.create_preview()?,
+            run_state,
+            last_saved: None,
+        };
+        let _server = self.start_status_server(&control)?;
+
+        // Setup a camera based on the manifest; from here on it is always shut down
+        let mut camera = self.camera_factory.create_camera()?;
+        let mut last_retention: u128 = 0;
+        let mut halt: Option<AppError> = None;
 
         for sample in probe {
             let time_since_start = sample.elapsed;
 
-            info!(self.logger, "snapshot start: {}", sample.utc.to_rfc2822());
-            let sample_start = Instant::now();
+            // Make room before saving, or stop if there is none
+            if let Some(retention) = retention.as_ref() {
+                let check_interval = self.manifest.retention.check_interval as u128;
+                if sample.elapsed - last_retention >= check_interval {
+                    last_retention = sample.elapsed;
+                    if let Err(err) = self.apply_retention(retention, sample.timestamp) {
+                        halt = Some(err);
+                        break;
+                    }
+                }
+            }
 
-            // Take a picture
-            let frame = camera.next()?;
-            let sample_end = Instant::now();
-            let elapsed = (sample_end - sample_start).as_millis();
-            info!(
-                self.logger,
-                "captured: {}x{} image in {}ms",
-                frame.width(),
-                frame.height(),
-                elapsed
-            );
+            // Stop on any error, but still shut the camera down below
+            if let Err(err) = self.capture_sample(camera.as_mut(), &mut state, &sample) {
+                error!(self.logger, "{:?}; halting capture", err);
+                self.report_error(format!("{:?}", err));
+                halt = Some(err);
+                break;
+            }
 
-            // Save the picture
-            image_logger.save(frame, sample)?;
-            let sample_end = Instant::now();
-            let elapsed = (sample_end - sample_start).as_millis();
-            info!(self.logger, "wrote image in {}ms", elapsed);
+            if let Ok(mut status) = self.status.lock() {
+                let interval = control.interval().unwrap_or(interval);
+                status.next_sample = if control.is_paused() {
+                    None
+                } else {
+                    Some(sample.utc + chrono::Duration::milliseconds(interval as i64))
+                };
+            }
 
             let hours = time_since_start / 1000 / 60 / 60;
-            let mins = time_since_start / 1000 / 60 - hours * 60;
-            let secs = time_since_start / 1000 - mins * 60;
+            let mins = time_since_start / 1000 / 60 % 60;
+            let secs = time_since_start / 1000 % 60;
             info!(
                 self.logger,
                 "snapshot end: {} hours, {} min, {} sec since start", hours, mins, secs
             );
         }
 
-        info!(self.logger, "Lock removed; halting capture");
+        if halt.is_some() {
+            // Already logged by apply_retention or capture_sample
+        } else if shutdown_requested() {
+            info!(self.logger, "Shutdown signal received; halting capture");
+        } else if control.is_stopped() {
+            info!(self.logger, "Stop requested; halting capture");
+        } else {
+            info!(self.logger, "Lock removed; halting capture");
+        }
         camera.shutdown()?;
 
-        Ok(())
+        match halt {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
 }
 
 pub mod error {
+    use crate::analysis::AnalysisError;
+    use crate::encoding::error::EncodingError;
     use crate::hardware::HardwareError;
-    use crate::resources::{LockError, ResourceError, TimeProbe, TimeProbeError};
+    use crate::resources::{LockError, ResourceError, RetentionError, TimeProbe, TimeProbeError};
     use image::ImageError;
     use sloggers::Error;
     use std::fmt;
@@ -181,9 +580,162 @@ pub mod error {
         }
     }
 
+    impl From<AnalysisError> for AppError {
+        fn from(err: AnalysisError) -> Self {
+            AppError::InvalidResource(format!("{:?}", err))
+        }
+    }
+
+    impl From<EncodingError> for AppError {
+        fn from(err: EncodingError) -> Self {
+            AppError::InvalidResource(format!("{:?}", err))
+        }
+    }
+
+    impl From<RetentionError> for AppError {
+        fn from(err: RetentionError) -> Self {
+            match err {
+                RetentionError::OutOfSpace { .. } => AppError::OutputError(format!("{}", err)),
+                _ => AppError::InvalidResource(format!("{:?}", err)),
+            }
+        }
+    }
+
     impl From<ImageError> for AppError {
         fn from(err: ImageError) -> Self {
             AppError::OutputError(format!("failed to save frame: {:?}", err))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::config::Manifest;
+    use super::error::AppError;
+    use super::App;
+    use crate::app::frame_recovery::is_complete_png;
+    use crate::hardware::{CameraLike, CameraRegistry, Frame, HardwareError};
+    use crate::resources::{lock_signals_for_test, set_shutdown_requested, ConfigMap};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    fn manifest(folder: &str, settings: &str) -> Manifest {
+        let _ = fs::remove_dir_all(folder);
+        toml::from_str(&format!(
+            r#"
+            [config]
+            output_folder = "{0}/frames"
+            log_folder = "{0}/logs"
+            lock_file = "{0}/lock"
+            sample_interval = 1000
+            sample_idle = 5
+            use_ntp = false
+            time_scale = 100.0
+            resume = false
+
+            [export]
+            export_file = "{0}/export.webm"
+            export_framerate = 24
+
+            [settings]
+            backend = "pattern"
+            resolution = "64x48"
+            {1}
+            "#,
+            folder, settings
+        ))
+        .unwrap()
+    }
+
+    fn saved_frames(folder: &str) -> Vec<PathBuf> {
+        let mut frames: Vec<PathBuf> = fs::read_dir(format!("{}/frames", folder))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().map(|e| e == "png") == Some(true))
+            .collect();
+        frames.sort();
+        frames
+    }
+
+    /// A pattern camera which raises the shutdown signal while taking a frame
+    struct SignalledCamera {
+        inner: Box<dyn CameraLike + 'static>,
+        frames: u32,
+        signal_on: u32,
+        shutdown: Arc<AtomicBool>,
+    }
+
+    impl CameraLike for SignalledCamera {
+        fn initialize(&mut self, config: ConfigMap) -> Result<(), HardwareError> {
+            self.inner.initialize(config)
+        }
+
+        fn shutdown(&mut self) -> Result<(), HardwareError> {
+            self.shutdown.store(true, Ordering::SeqCst);
+            self.inner.shutdown()
+        }
+
+        fn next(&mut self) -> Result<Frame, HardwareError> {
+            self.frames += 1;
+            if self.frames == self.signal_on {
+                set_shutdown_requested(true);
+            }
+            self.inner.next()
+        }
+    }
+
+    #[test]
+    pub fn test_signal_finishes_capture() {
+        let _signals = lock_signals_for_test();
+        let folder = "test/output/app_signal";
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let mut registry = CameraRegistry::default();
+        let camera_shutdown = shutdown.clone();
+        registry.register("pattern", Vec::new(), move || {
+            Ok(Box::new(SignalledCamera {
+                inner: CameraRegistry::default().create("pattern", &ConfigMap::new())?,
+                frames: 0,
+                signal_on: 3,
+                shutdown: camera_shutdown.clone(),
+            }))
+        });
+
+        let mut app = App::with_registry(manifest(folder, ""), registry).unwrap();
+        let result = app.run();
+        set_shutdown_requested(false);
+        result.unwrap();
+
+        // The frame being taken when the signal arrived is still saved in full
+        let frames = saved_frames(folder);
+        assert_eq!(frames.len(), 3);
+        assert!(frames.iter().all(|path| is_complete_png(path)));
+        assert!(shutdown.load(Ordering::SeqCst));
+        assert!(!Path::new(&format!("{}/lock", folder)).exists());
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    pub fn test_faults_during_capture() {
+        let _signals = lock_signals_for_test();
+        let folder = "test/output/app_faults";
+        let manifest = manifest(
+            folder,
+            r#"fault_plan = "ok,resize:32x16,latency:20,error:device_lost,ok""#,
+        );
+
+        // Resized and slow frames are saved as they are; a lost device halts the capture
+        match App::new(manifest).unwrap().run() {
+            Err(AppError::DeviceFailed(message)) => assert!(message.contains("frame 3")),
+            other => panic!("expected the device to fail, got {:?}", other),
+        }
+        let sizes: Vec<(u32, u32)> = saved_frames(folder)
+            .iter()
+            .map(|path| image::image_dimensions(path).unwrap())
+            .collect();
+        assert_eq!(sizes, vec![(64, 48), (32, 16), (64, 48)]);
+        assert!(!Path::new(&format!("{}/lock", folder)).exists());
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
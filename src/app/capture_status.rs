@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// What the capture loop is doing, shared with the status server
+#[derive(Debug, Clone)]
+pub struct CaptureStatus {
+    pub started: DateTime<Utc>,
+    pub frame_count: u64,
+    pub last_frame: Option<PathBuf>,
+    pub last_frame_time: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub next_sample: Option<DateTime<Utc>>,
+    pub paused: bool,
+    pub interval: u64,
+}
+
+pub type SharedStatus = Arc<Mutex<CaptureStatus>>;
+
+impl Default for CaptureStatus {
+    fn default() -> Self {
+        CaptureStatus {
+            started: Utc::now(),
+            frame_count: 0,
+            last_frame: None,
+            last_frame_time: None,
+            last_error: None,
+            next_sample: None,
+            paused: false,
+            interval: 0,
+        }
+    }
+}
+
+impl CaptureStatus {
+    pub fn shared() -> SharedStatus {
+        Arc::new(Mutex::new(Default::default()))
+    }
+
+    pub fn to_json(&self, disk_free: Option<u64>) -> String {
+        let fields = [
+            ("started", json_string(&self.started.to_rfc3339())),
+            (
+                "uptime",
+                format!("{}", (Utc::now() - self.started).num_seconds()),
+            ),
+            ("frame_count", format!("{}", self.frame_count)),
+            (
+                "last_frame",
+                json_option(
+                    self.last_frame
+                        .as_ref()
+                        .and_then(|path| path.file_name())
+                        .map(|name| name.to_string_lossy().to_string()),
+                ),
+            ),
+            (
+                "last_frame_time",
+                json_option(self.last_frame_time.map(|t| t.to_rfc3339())),
+            ),
+            ("last_error", json_option(self.last_error.clone())),
+            (
+                "next_sample",
+                json_option(self.next_sample.map(|t| t.to_rfc3339())),
+            ),
+            ("paused", format!("{}", self.paused)),
+            ("interval", format!("{}", self.interval)),
+            (
+                "disk_free",
+                disk_free
+                    .map(|bytes| format!("{}", bytes))
+                    .unwrap_or_else(|| "null".to_string()),
+            ),
+        ];
+        let body: Vec<String> = fields
+            .iter()
+            .map(|(key, value)| format!("\"{}\":{}", key, value))
+            .collect();
+        format!("{{{}}}", body.join(","))
+    }
+}
+
+fn json_option(value: Option<String>) -> String {
+    match value {
+        Some(v) => json_string(&v),
+        None => "null".to_string(),
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut output = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaptureStatus;
+    use std::path::PathBuf;
+
+    #[test]
+    pub fn test_status_json() {
+        let status = CaptureStatus {
+            frame_count: 3,
+            last_frame: Some(PathBuf::from("output/123-a.png")),
+            last_error: Some("rejected \"black\" frame".to_string()),
+            ..Default::default()
+        };
+        let json = status.to_json(Some(1024));
+        assert!(json.contains("\"frame_count\":3"));
+        assert!(json.contains("\"last_frame\":\"123-a.png\""));
+        assert!(json.contains("\"last_error\":\"rejected \\\"black\\\" frame\""));
+        assert!(json.contains("\"next_sample\":null"));
+        assert!(json.contains("\"paused\":false"));
+        assert!(json.contains("\"disk_free\":1024"));
+    }
+}
@@ -6,6 +6,34 @@ pub struct Manifest {
 
     pub export: ManifestExport,
 
+    /// Frame validation; off unless the section is present
+    #[serde(default)]
+    pub validation: ManifestValidation,
+
+    /// Change triggered capture; off unless the section is present
+    #[serde(default)]
+    pub motion: ManifestMotion,
+
+    /// Text burned into frames; off unless the section is present
+    #[serde(default)]
+    pub overlay: ManifestOverlay,
+
+    /// Rolling preview of the latest frames; off unless the section is present
+    #[serde(default)]
+    pub preview: ManifestPreview,
+
+    /// Embedded http status server; off unless the section is present
+    #[serde(default)]
+    pub server: ManifestServer,
+
+    /// Layout of the sheets made by contact-sheet
+    #[serde(default)]
+    pub contact_sheet: ManifestContactSheet,
+
+    /// Limits on the frames kept in the output folder; off unless the section is present
+    #[serde(default)]
+    pub retention: ManifestRetention,
+
     /// Device settings
     pub settings: HashMap<String, String>,
 }
@@ -17,6 +45,83 @@ pub struct ManifestExport {
 
     /// The framerate to export with
     pub export_framerate: u32,
+
+    /// Drop near duplicate frames before encoding
+    #[serde(default)]
+    pub dedup: bool,
+
+    /// Frames whose perceptual hashes differ by this many bits (of 64) or less are duplicates
+    #[serde(default = "self::defaults::dedup_threshold")]
+    pub dedup_threshold: u32,
+
+    /// drop to keep one frame of each run of duplicates, or collapse:N to keep N
+    #[serde(default = "self::defaults::dedup_mode")]
+    pub dedup_mode: String,
+
+    /// Where to write the list of removed frames; defaults to the export file + .dedup.txt
+    #[serde(default)]
+    pub dedup_report: Option<String>,
+
+    /// Scratch folder for frames being exported; defaults to 'staging' in the output folder
+    #[serde(default)]
+    pub staging_folder: Option<String>,
+
+    /// Only encode frames captured since the last run, and append them to the output
+    #[serde(default)]
+    pub incremental: bool,
+
+    /// Where to record which frames have been encoded; defaults to the export file + .state
+    #[serde(default)]
+    pub state_file: Option<String>,
+
+    /// Where to keep the encoded segments; defaults to 'segments' in the output folder
+    #[serde(default)]
+    pub segment_folder: Option<String>,
+
+    /// Align frames to remove camera drift and shake, and crop to the stable region
+    #[serde(default)]
+    pub stabilize: bool,
+
+    /// first to align every frame with the first frame, or previous to align each frame
+    /// with the one before it
+    #[serde(default = "self::defaults::stabilize_reference")]
+    pub stabilize_reference: String,
+
+    /// Also correct rotations of up to this many degrees; 0 for translation only
+    #[serde(default)]
+    pub stabilize_rotation: f32,
+
+    /// Ignore offsets larger than this fraction of the frame, as failed matches
+    #[serde(default = "self::defaults::stabilize_max_shift")]
+    pub stabilize_max_shift: f32,
+
+    /// Crop to the region covered by every frame; otherwise the edges are filled with black
+    #[serde(default = "self::defaults::stabilize_crop")]
+    pub stabilize_crop: bool,
+
+    /// Smooth out exposure and white balance changes between frames
+    #[serde(default)]
+    pub deflicker: bool,
+
+    /// How many frames either side of each frame to smooth over
+    #[serde(default = "self::defaults::deflicker_window")]
+    pub deflicker_window: usize,
+
+    /// mean or median
+    #[serde(default = "self::defaults::deflicker_method")]
+    pub deflicker_method: String,
+
+    /// Average each frame with this many frames before it; 0 or 1 to disable
+    #[serde(default)]
+    pub average_frames: usize,
+
+    /// Insert this many cross faded frames between each pair of frames
+    #[serde(default)]
+    pub blend_frames: u32,
+
+    /// Show each frame this many times; 0 or 1 to disable
+    #[serde(default)]
+    pub duplicate_frames: u32,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -39,10 +144,453 @@ pub struct ManifestConfig {
     /// Scale time for testing, typically set this to 1
     #[serde(default = "self::defaults::time_scale")]
     pub time_scale: f32,
+
+    /// Where to move unfinished or truncated frames found at startup; defaults to a
+    /// 'quarantine' folder in the output folder.
+    #[serde(default)]
+    pub quarantine_folder: Option<String>,
+
+    /// Save frames in dated subfolders of the output folder, eg. YYYY/MM/DD/HH; flat if empty
+    #[serde(default)]
+    pub folder_layout: String,
+
+    /// Carry on the session saved in run_state_file, rather than starting a new one
+    #[serde(default = "self::defaults::resume")]
+    pub resume: bool,
+
+    /// Where to save the session; defaults to session.toml in the output folder
+    #[serde(default)]
+    pub run_state_file: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ManifestValidation {
+    /// Check each frame for black, blank, green or partial frames before saving it
+    #[serde(default)]
+    pub validate_frames: bool,
+
+    /// How many times to retry the capture when a frame is rejected.
+    #[serde(default = "self::defaults::retries")]
+    pub retries: u32,
+
+    /// Where to save rejected frames; defaults to a 'rejected' folder in the output folder.
+    #[serde(default)]
+    pub rejected_folder: Option<String>,
+
+    /// Mean luminance (0-255) below which a frame is black
+    #[serde(default = "self::defaults::min_luminance")]
+    pub min_luminance: f32,
+
+    /// Luminance variance below which a frame is blank
+    #[serde(default = "self::defaults::min_variance")]
+    pub min_variance: f32,
+
+    /// Fraction of green pixels above which a frame is green
+    #[serde(default = "self::defaults::max_green_ratio")]
+    pub max_green_ratio: f32,
+
+    /// Fraction of flat rows at the bottom above which a frame is partially decoded
+    #[serde(default = "self::defaults::max_flat_tail")]
+    pub max_flat_tail: f32,
+}
+
+impl Default for ManifestValidation {
+    fn default() -> Self {
+        ManifestValidation {
+            validate_frames: false,
+            retries: defaults::retries(),
+            rejected_folder: None,
+            min_luminance: defaults::min_luminance(),
+            min_variance: defaults::min_variance(),
+            max_green_ratio: defaults::max_green_ratio(),
+            max_flat_tail: defaults::max_flat_tail(),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ManifestMotion {
+    /// Poll the camera and only save frames which differ from the last saved frame
+    #[serde(default)]
+    pub capture_on_change: bool,
+
+    /// How to compare frames; pixel, histogram or phash
+    #[serde(default = "self::defaults::change_metric")]
+    pub change_metric: String,
+
+    /// How different (0 to 1) a frame must be from the last saved frame to be saved
+    #[serde(default = "self::defaults::change_threshold")]
+    pub change_threshold: f32,
+
+    /// How long between polling the camera in ms; replaces sample_interval
+    #[serde(default = "self::defaults::poll_interval")]
+    pub poll_interval: u64,
+
+    /// Never save frames closer together than this in ms
+    #[serde(default)]
+    pub min_interval: u64,
+
+    /// Save a frame at least this often in ms even if nothing changed; 0 to never
+    #[serde(default)]
+    pub max_interval: u64,
+}
+
+impl Default for ManifestMotion {
+    fn default() -> Self {
+        ManifestMotion {
+            capture_on_change: false,
+            change_metric: defaults::change_metric(),
+            change_threshold: defaults::change_threshold(),
+            poll_interval: defaults::poll_interval(),
+            min_interval: 0,
+            max_interval: 0,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ManifestOverlay {
+    /// Draw the overlay on frames as they are saved
+    #[serde(default)]
+    pub overlay_capture: bool,
+
+    /// Draw the overlay on frames when they are exported by assemble
+    #[serde(default)]
+    pub overlay_export: bool,
+
+    /// The text to draw; may contain {utc}, {local}, {date}, {time} and {camera}
+    #[serde(default = "self::defaults::overlay_text")]
+    pub text: String,
+
+    /// The name used for {camera}; defaults to the camera backend
+    #[serde(default)]
+    pub camera_name: Option<String>,
+
+    /// top-left, top-right, bottom-left or bottom-right
+    #[serde(default = "self::defaults::overlay_position")]
+    pub position: String,
+
+    /// The size of each font pixel
+    #[serde(default = "self::defaults::overlay_scale")]
+    pub scale: u32,
+
+    /// The text colour, as "#rrggbb" or "r,g,b"
+    #[serde(default = "self::defaults::overlay_color")]
+    pub color: String,
+
+    /// The colour of the box behind the text; empty for no box
+    #[serde(default = "self::defaults::overlay_background")]
+    pub background: String,
+}
+
+impl Default for ManifestOverlay {
+    fn default() -> Self {
+        ManifestOverlay {
+            overlay_capture: false,
+            overlay_export: false,
+            text: defaults::overlay_text(),
+            camera_name: None,
+            position: defaults::overlay_position(),
+            scale: defaults::overlay_scale(),
+            color: defaults::overlay_color(),
+            background: defaults::overlay_background(),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ManifestPreview {
+    /// Keep an animated GIF of the latest frames up to date while capturing
+    #[serde(default)]
+    pub live_preview: bool,
+
+    /// Where to write the preview; defaults to preview.gif in the output folder
+    #[serde(default)]
+    pub preview_file: Option<String>,
+
+    /// How many of the latest frames to show
+    #[serde(default = "self::defaults::preview_frames")]
+    pub preview_frames: usize,
+
+    /// The width of the preview; the height follows the frames
+    #[serde(default = "self::defaults::preview_width")]
+    pub preview_width: u32,
+
+    /// How long to show each frame for, in ms
+    #[serde(default = "self::defaults::preview_delay")]
+    pub preview_delay: u32,
+}
+
+impl Default for ManifestPreview {
+    fn default() -> Self {
+        ManifestPreview {
+            live_preview: false,
+            preview_file: None,
+            preview_frames: defaults::preview_frames(),
+            preview_width: defaults::preview_width(),
+            preview_delay: defaults::preview_delay(),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ManifestRetention {
+    /// Delete the oldest frames once the frames take up more than this; 0 for no limit
+    #[serde(default)]
+    pub max_total_mb: u64,
+
+    /// Delete frames older than this many days; 0 to keep them
+    #[serde(default)]
+    pub max_age_days: u64,
+
+    /// Thin frames older than this many days to one per thin_interval; 0 to keep them all
+    #[serde(default)]
+    pub thin_after_days: u64,
+
+    /// The time between the frames kept when thinning, in ms
+    #[serde(default = "self::defaults::thin_interval")]
+    pub thin_interval: u64,
+
+    /// Keep at least this much space free on the disk; 0 for no limit
+    #[serde(default)]
+    pub min_free_mb: u64,
+
+    /// stop to halt the capture when space runs low, or delete-oldest to make room
+    #[serde(default = "self::defaults::low_space")]
+    pub low_space: String,
+
+    /// How often to check the limits while capturing, in ms
+    #[serde(default = "self::defaults::retention_check_interval")]
+    pub check_interval: u64,
+}
+
+impl Default for ManifestRetention {
+    fn default() -> Self {
+        ManifestRetention {
+            max_total_mb: 0,
+            max_age_days: 0,
+            thin_after_days: 0,
+            thin_interval: defaults::thin_interval(),
+            min_free_mb: 0,
+            low_space: defaults::low_space(),
+            check_interval: defaults::retention_check_interval(),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ManifestServer {
+    /// Serve the latest frame, status and a gallery over http while capturing
+    #[serde(default)]
+    pub http_server: bool,
+
+    /// The address to listen on
+    #[serde(default = "self::defaults::server_bind")]
+    pub bind: String,
+
+    /// How many frames to show on each page of the gallery
+    #[serde(default = "self::defaults::gallery_page_size")]
+    pub gallery_page_size: usize,
+
+    /// Accept pause, resume, stop, capture and interval requests under /control
+    #[serde(default)]
+    pub remote_control: bool,
+
+    /// If set, control requests must send this as a bearer token
+    #[serde(default)]
+    pub control_token: Option<String>,
+}
+
+impl Default for ManifestServer {
+    fn default() -> Self {
+        ManifestServer {
+            http_server: false,
+            bind: defaults::server_bind(),
+            gallery_page_size: defaults::gallery_page_size(),
+            remote_control: false,
+            control_token: None,
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct ManifestContactSheet {
+    /// Where to write the sheets; defaults to 'contact_sheets' in the output folder
+    #[serde(default)]
+    pub output_folder: Option<String>,
+
+    /// How many frames across each row
+    #[serde(default = "self::defaults::sheet_columns")]
+    pub columns: u32,
+
+    /// The width of each frame on the sheet
+    #[serde(default = "self::defaults::sheet_thumbnail_width")]
+    pub thumbnail_width: u32,
+
+    /// Show at most this many frames per sheet, evenly spaced; 0 for every frame
+    #[serde(default = "self::defaults::sheet_max_frames")]
+    pub max_frames: usize,
+
+    /// The format of the local time under each frame
+    #[serde(default = "self::defaults::sheet_caption_format")]
+    pub caption_format: String,
+
+    /// png or jpg
+    #[serde(default = "self::defaults::sheet_format")]
+    pub format: String,
+}
+
+impl Default for ManifestContactSheet {
+    fn default() -> Self {
+        ManifestContactSheet {
+            output_folder: None,
+            columns: defaults::sheet_columns(),
+            thumbnail_width: defaults::sheet_thumbnail_width(),
+            max_frames: defaults::sheet_max_frames(),
+            caption_format: defaults::sheet_caption_format(),
+            format: defaults::sheet_format(),
+        }
+    }
 }
 
 mod defaults {
     pub fn time_scale() -> f32 {
         1f32
     }
+
+    pub fn retries() -> u32 {
+        3
+    }
+
+    pub fn min_luminance() -> f32 {
+        8.0
+    }
+
+    pub fn min_variance() -> f32 {
+        4.0
+    }
+
+    pub fn max_green_ratio() -> f32 {
+        0.9
+    }
+
+    pub fn max_flat_tail() -> f32 {
+        0.25
+    }
+
+    pub fn change_metric() -> String {
+        "pixel".to_string()
+    }
+
+    pub fn change_threshold() -> f32 {
+        0.05
+    }
+
+    pub fn poll_interval() -> u64 {
+        1000
+    }
+
+    pub fn dedup_threshold() -> u32 {
+        4
+    }
+
+    pub fn dedup_mode() -> String {
+        "drop".to_string()
+    }
+
+    pub fn stabilize_reference() -> String {
+        "first".to_string()
+    }
+
+    pub fn stabilize_max_shift() -> f32 {
+        0.1
+    }
+
+    pub fn stabilize_crop() -> bool {
+        true
+    }
+
+    pub fn deflicker_window() -> usize {
+        7
+    }
+
+    pub fn deflicker_method() -> String {
+        "median".to_string()
+    }
+
+    pub fn overlay_text() -> String {
+        "{camera} {local}".to_string()
+    }
+
+    pub fn overlay_position() -> String {
+        "bottom-left".to_string()
+    }
+
+    pub fn overlay_scale() -> u32 {
+        2
+    }
+
+    pub fn overlay_color() -> String {
+        "#ffffff".to_string()
+    }
+
+    pub fn overlay_background() -> String {
+        "#000000".to_string()
+    }
+
+    pub fn sheet_columns() -> u32 {
+        8
+    }
+
+    pub fn sheet_thumbnail_width() -> u32 {
+        240
+    }
+
+    pub fn sheet_max_frames() -> usize {
+        96
+    }
+
+    pub fn sheet_caption_format() -> String {
+        "%H:%M".to_string()
+    }
+
+    pub fn sheet_format() -> String {
+        "png".to_string()
+    }
+
+    pub fn preview_frames() -> usize {
+        48
+    }
+
+    pub fn preview_width() -> u32 {
+        320
+    }
+
+    pub fn preview_delay() -> u32 {
+        100
+    }
+
+    pub fn server_bind() -> String {
+        "0.0.0.0:8080".to_string()
+    }
+
+    pub fn gallery_page_size() -> usize {
+        50
+    }
+
+    pub fn thin_interval() -> u64 {
+        60 * 60 * 1000
+    }
+
+    pub fn low_space() -> String {
+        "stop".to_string()
+    }
+
+    pub fn retention_check_interval() -> u64 {
+        10 * 60 * 1000
+    }
+
+    pub fn resume() -> bool {
+        true
+    }
 }
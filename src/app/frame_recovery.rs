@@ -0,0 +1,143 @@
+use crate::app::error::AppError;
+use crate::resources::{is_temp_path, ResourceFolder};
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// The last chunk of every complete png: a zero length IEND chunk and its crc
+const PNG_TRAILER: [u8; 12] = [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82];
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// True if the file starts with the png signature and ends with the IEND chunk, which is
+/// enough to catch frames cut short by a crash or power loss without decoding every frame.
+pub fn is_complete_png(path: &Path) -> bool {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+    let mut signature = [0u8; 8];
+    let mut trailer = [0u8; 12];
+    file.read_exact(&mut signature).is_ok()
+        && signature == PNG_SIGNATURE
+        && file.seek(SeekFrom::End(-12)).is_ok()
+        && file.read_exact(&mut trailer).is_ok()
+        && trailer == PNG_TRAILER
+}
+
+/// Move unfinished temp files and truncated frames out of the output folder and its dated
+/// subfolders, so they don't break assemble. Returns the new paths of the quarantined files.
+///
+/// Frames are written one at a time, so anything cut short by a crash is newer than the
+/// newest complete frame; the walk goes back from the newest file and stops there.
+pub fn quarantine_incomplete(
+    output: &ResourceFolder,
+    quarantine: &ResourceFolder,
+) -> Result<Vec<PathBuf>, AppError> {
+    let base = output.basepath()?;
+    let mut moved = Vec::new();
+    for path in output.walk_newest()? {
+        let path = path?;
+        if path.extension() == Some(OsStr::new("png")) && is_complete_png(&path) {
+            break;
+        }
+        let broken = is_temp_path(&path) || path.extension() == Some(OsStr::new("png"));
+        if !broken {
+            continue;
+        }
+        let target = quarantine_path(&base, &path, quarantine)?;
+        fs::rename(&path, &target).map_err(|err| {
+            AppError::OutputError(format!("failed to quarantine {:?}: {}", path, err))
+        })?;
+        moved.push(target);
+    }
+    Ok(moved)
+}
+
+/// Where to move a file: the same path relative to the output folder, without the leading
+/// '.' of a temp file, and with a number added if an earlier crash left a file there.
+fn quarantine_path(
+    base: &Path,
+    path: &Path,
+    quarantine: &ResourceFolder,
+) -> Result<PathBuf, AppError> {
+    let relative = path.strip_prefix(base).unwrap_or(path);
+    let name = relative
+        .file_name()
+        .map(|n| n.to_string_lossy().trim_start_matches('.').to_string())
+        .unwrap_or_default();
+    let folder = quarantine
+        .basepath()?
+        .join(relative.parent().unwrap_or(Path::new("")));
+    fs::create_dir_all(&folder)
+        .map_err(|err| AppError::OutputError(format!("failed to create {:?}: {}", folder, err)))?;
+    let mut target = folder.join(&name);
+    let mut copy = 1;
+    while target.exists() {
+        target = folder.join(format!("{}.{}", copy, name));
+        copy += 1;
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_complete_png, quarantine_incomplete};
+    use crate::resources::ResourceFolder;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    pub fn test_quarantine_incomplete() {
+        let folder = "test/output/recovery";
+        let _ = fs::remove_dir_all(folder);
+        fs::create_dir_all(folder).unwrap();
+        let source = Path::new("test/data/frames/frame_00000000.png");
+        assert!(is_complete_png(source));
+
+        let frame = fs::read(source).unwrap();
+        fs::write(format!("{}/100-good.png", folder), &frame).unwrap();
+        fs::write(format!("{}/200-cut.png", folder), &frame[..frame.len() / 2]).unwrap();
+        fs::write(format!("{}/.300-temp.png.partial", folder), &frame[..10]).unwrap();
+        fs::write(format!("{}/notes.txt", folder), b"kept").unwrap();
+
+        let output = ResourceFolder::new(folder).require_existing().unwrap();
+        let quarantine = ResourceFolder::new(&format!("{}/quarantine", folder))
+            .require()
+            .unwrap();
+        let mut moved = quarantine_incomplete(&output, &quarantine).unwrap();
+        moved.sort();
+
+        assert_eq!(moved.len(), 2);
+        assert!(moved[0].ends_with("200-cut.png"));
+        assert!(moved[1].ends_with("300-temp.png.partial"));
+        assert!(Path::new(&format!("{}/100-good.png", folder)).exists());
+        assert!(Path::new(&format!("{}/notes.txt", folder)).exists());
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    pub fn test_quarantine_keeps_earlier_files() {
+        let folder = "test/output/recovery_repeat";
+        let _ = fs::remove_dir_all(folder);
+        let frame = fs::read("test/data/frames/frame_00000000.png").unwrap();
+        let output = ResourceFolder::new(folder).require().unwrap();
+        let quarantine = ResourceFolder::new(&format!("{}/quarantine", folder))
+            .require()
+            .unwrap();
+
+        for crash in 0..2 {
+            fs::create_dir_all(format!("{}/2021/03", folder)).unwrap();
+            fs::write(format!("{}/2021/03/100-good.png", folder), &frame).unwrap();
+            fs::write(format!("{}/2021/03/.200-x.png.partial", folder), [crash]).unwrap();
+            let moved = quarantine_incomplete(&output, &quarantine).unwrap();
+            assert_eq!(moved.len(), 1);
+        }
+
+        let first = format!("{}/quarantine/2021/03/200-x.png.partial", folder);
+        let second = format!("{}/quarantine/2021/03/1.200-x.png.partial", folder);
+        assert_eq!(fs::read(first).unwrap(), vec![0]);
+        assert_eq!(fs::read(second).unwrap(), vec![1]);
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -1,12 +1,22 @@
+use crate::analysis::Rejection;
 use crate::app::error::AppError;
+use crate::encoding::Overlay;
 use crate::hardware::Frame;
-use crate::resources::{ResourceFolder, TimeSnapshot};
+use crate::resources::{write_atomic, FolderLayout, ResourceFolder, TimeSnapshot};
+use image::codecs::png::PngEncoder;
+use image::{ColorType, RgbImage};
 use slog::error;
 use slog::Logger;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::thread;
 
 pub struct ImageLogger {
     output_folder: ResourceFolder,
+    rejected_folder: Option<ResourceFolder>,
+    overlay: Option<Overlay>,
+    layout: FolderLayout,
     logger: Logger,
 }
 
@@ -14,14 +24,78 @@ impl ImageLogger {
     pub fn new(output_folder: ResourceFolder, logger: Logger) -> ImageLogger {
         ImageLogger {
             output_folder,
+            rejected_folder: None,
+            overlay: None,
+            layout: FolderLayout::flat(),
             logger,
         }
     }
 
-    pub(crate) fn save(&self, frame: Frame, timestamp: TimeSnapshot) -> Result<(), AppError> {
+    /// Keep frames which fail validation in this folder, instead of dropping them.
+    pub fn with_rejected_folder(mut self, rejected_folder: ResourceFolder) -> ImageLogger {
+        self.rejected_folder = Some(rejected_folder);
+        self
+    }
+
+    /// Draw this overlay on every saved frame.
+    pub fn with_overlay(mut self, overlay: Overlay) -> ImageLogger {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Save frames into dated subfolders of the output folder.
+    pub fn with_layout(mut self, layout: FolderLayout) -> ImageLogger {
+        self.layout = layout;
+        self
+    }
+
+    /// Save a frame, returning the path it was saved to.
+    pub(crate) fn save(&self, frame: Frame, timestamp: &TimeSnapshot) -> Result<PathBuf, AppError> {
         let filename = format!("{}-{}.png", timestamp.timestamp, timestamp.utc.to_rfc2822());
-        let filepath = self.output_folder.path(&filename)?;
-        frame.save(filepath)?;
+        let folder = self
+            .output_folder
+            .basepath()?
+            .join(self.layout.subfolder(&timestamp.utc));
+        fs::create_dir_all(&folder).map_err(|err| {
+            AppError::OutputError(format!("failed to create {:?}: {}", folder, err))
+        })?;
+        let filepath = folder.join(&filename);
+        match self.overlay.as_ref() {
+            Some(overlay) => {
+                let mut image =
+                    RgbImage::from_raw(frame.width(), frame.height(), frame.to_vec())
+                        .ok_or_else(|| AppError::OutputError("invalid frame buffer".to_string()))?;
+                overlay.render(&mut image, &timestamp.utc);
+                write_png(&filepath, &image, image.width(), image.height())?;
+            }
+            None => write_png(&filepath, &frame, frame.width(), frame.height())?,
+        }
+        Ok(filepath)
+    }
+
+    /// Save a rejected frame, with the reason it was rejected in the filename.
+    pub(crate) fn reject(
+        &self,
+        frame: Frame,
+        timestamp: &TimeSnapshot,
+        attempt: u32,
+        reason: &Rejection,
+    ) -> Result<(), AppError> {
+        if let Some(folder) = self.rejected_folder.as_ref() {
+            let filename = format!("{}-{}-{}.png", timestamp.timestamp, attempt, reason);
+            let filepath = folder.path(&filename)?;
+            write_png(&filepath, &frame, frame.width(), frame.height())?;
+        }
         Ok(())
     }
 }
+
+/// Encode an rgb buffer as a png, so that a crash never leaves a partly written frame
+fn write_png(path: &Path, buffer: &[u8], width: u32, height: u32) -> Result<(), AppError> {
+    write_atomic(path, |writer| {
+        PngEncoder::new(writer)
+            .encode(buffer, width, height, ColorType::Rgb8)
+            .map_err(|err| io::Error::other(format!("{}", err)))
+    })
+    .map_err(|err| AppError::OutputError(format!("failed to save frame: {}", err)))
+}
@@ -0,0 +1,110 @@
+use crate::app::error::AppError;
+use crate::resources::write_atomic;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The capture session, saved in the output folder so a restarted snapshot carries on with
+/// the same start time, frame numbering and schedule.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RunState {
+    /// When the session started, in ms since the epoch
+    pub started: u64,
+
+    /// How many frames have been saved in the session
+    pub sequence: u64,
+
+    /// When the last frame was saved, in ms since the epoch
+    pub last_capture: Option<u64>,
+}
+
+/// A `RunState` and the file it is kept in
+pub struct RunStateFile {
+    path: PathBuf,
+    state: RunState,
+    resumed: bool,
+}
+
+impl RunStateFile {
+    /// Load the session from path, or start a new one at now (ms since the epoch) if there
+    /// is no saved session or resume is false
+    pub fn open(path: &Path, now: u64, resume: bool) -> Result<RunStateFile, AppError> {
+        let saved = if resume && path.exists() {
+            let raw = fs::read_to_string(path).map_err(|err| {
+                AppError::InvalidResource(format!("failed to read {:?}: {}", path, err))
+            })?;
+            Some(toml::from_str::<RunState>(&raw).map_err(|err| {
+                AppError::InvalidResource(format!("invalid run state {:?}: {}", path, err))
+            })?)
+        } else {
+            None
+        };
+        let resumed = saved.is_some();
+        let state = saved.unwrap_or(RunState {
+            started: now,
+            ..Default::default()
+        });
+        let file = RunStateFile {
+            path: path.to_path_buf(),
+            state,
+            resumed,
+        };
+        file.save()?;
+        Ok(file)
+    }
+
+    pub fn state(&self) -> &RunState {
+        &self.state
+    }
+
+    /// True if the session was loaded from an earlier run
+    pub fn is_resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Record a saved frame, returning its sequence number in the session
+    pub fn record_capture(&mut self, timestamp: u64) -> Result<u64, AppError> {
+        self.state.sequence += 1;
+        self.state.last_capture = Some(timestamp);
+        self.save()?;
+        Ok(self.state.sequence)
+    }
+
+    fn save(&self) -> Result<(), AppError> {
+        let raw = toml::to_string(&self.state)
+            .map_err(|err| AppError::OutputError(format!("failed to save run state: {}", err)))?;
+        write_atomic(&self.path, |writer| writer.write_all(raw.as_bytes())).map_err(|err| {
+            AppError::OutputError(format!("failed to save run state {:?}: {}", self.path, err))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RunStateFile;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    pub fn test_resume_run_state() {
+        fs::create_dir_all("test/output").unwrap();
+        let path = Path::new("test/output/session.toml");
+        let _ = fs::remove_file(path);
+
+        let mut run = RunStateFile::open(path, 1000, true).unwrap();
+        assert!(!run.is_resumed());
+        assert_eq!(run.record_capture(2000).unwrap(), 1);
+        assert_eq!(run.record_capture(3000).unwrap(), 2);
+
+        let mut run = RunStateFile::open(path, 9000, true).unwrap();
+        assert!(run.is_resumed());
+        assert_eq!(run.state().started, 1000);
+        assert_eq!(run.state().last_capture, Some(3000));
+        assert_eq!(run.record_capture(10000).unwrap(), 3);
+
+        let run = RunStateFile::open(path, 20000, false).unwrap();
+        assert_eq!(run.state().started, 20000);
+        assert_eq!(run.state().sequence, 0);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,574 @@
+use crate::app::capture_status::SharedStatus;
+use crate::app::error::AppError;
+use crate::resources::{
+    available_space, CaptureControl, ControlCommand, FolderLayout, ResourceError, ResourceFolder,
+};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// A minimal http server for checking on a capture from a browser. It serves:
+///
+///   /              a page showing the latest frame and the status
+///   /latest.png    the latest frame
+///   /status.json   the capture status
+///   /gallery       the frames in the output folder, newest first; ?page=N
+///   /frames/PATH   a frame from the output folder, or one of its dated subfolders
+///
+/// If a control is given, it also accepts POST requests to change the capture:
+///
+///   /control/pause, /control/resume, /control/stop, /control/capture
+///   /control/interval?ms=N
+pub struct StatusServer {
+    port: u16,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+}
+
+struct Response {
+    status: u32,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Response {
+        Response {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    fn html(body: String) -> Response {
+        Response::ok("text/html; charset=utf-8", body.into_bytes())
+    }
+
+    fn error(status: u32, message: &str) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: message.as_bytes().to_vec(),
+        }
+    }
+}
+
+/// What each request handler can see
+#[derive(Clone)]
+struct Context {
+    status: SharedStatus,
+    output: ResourceFolder,
+    page_size: usize,
+    control: Option<CaptureControl>,
+    token: Option<String>,
+}
+
+impl StatusServer {
+    /// Listen on bind (eg. 0.0.0.0:8080, or 127.0.0.1:0 for any free port) and serve
+    /// requests on a background thread until the process exits. Control requests are only
+    /// accepted if control is given, and must carry token as a bearer token if it is given;
+    /// control without a token is only allowed on a loopback address.
+    pub fn start(
+        bind: &str,
+        status: SharedStatus,
+        output: ResourceFolder,
+        page_size: usize,
+        control: Option<CaptureControl>,
+        token: Option<String>,
+    ) -> Result<StatusServer, AppError> {
+        let listener = TcpListener::bind(bind)
+            .map_err(|err| AppError::NetworkFailed(format!("failed to bind {}: {}", bind, err)))?;
+        let address = listener
+            .local_addr()
+            .map_err(|err| AppError::NetworkFailed(format!("{}", err)))?;
+        if control.is_some() && token.is_none() && !address.ip().is_loopback() {
+            return Err(AppError::NetworkFailed(format!(
+                "remote control on {} would let anyone on the network stop the capture; \
+                 set a control_token or bind to 127.0.0.1",
+                bind
+            )));
+        }
+        let port = address.port();
+        let context = Context {
+            status,
+            output,
+            page_size: page_size.max(1),
+            control,
+            token,
+        };
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let _ = StatusServer::respond(stream, &context);
+            }
+        });
+        Ok(StatusServer { port })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    fn respond(mut stream: TcpStream, context: &Context) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let response = match StatusServer::read_request(&stream)? {
+            Some(request) => StatusServer::route(&request, context),
+            None => Response::error(400, "bad request"),
+        };
+        let reason = match response.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            500 => "Internal Server Error",
+            _ => "Error",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+            response.status,
+            reason,
+            response.content_type,
+            response.body.len()
+        )?;
+        stream.write_all(&response.body)
+    }
+
+    fn read_request(stream: &TcpStream) -> std::io::Result<Option<Request>> {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some(offset) = line.find(':') {
+                headers.insert(
+                    line[..offset].trim().to_lowercase(),
+                    line[offset + 1..].trim().to_string(),
+                );
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(m), Some(t)) => (m.to_string(), t),
+            _ => return Ok(None),
+        };
+        let (path, query) = match target.find('?') {
+            Some(offset) => (&target[..offset], &target[offset + 1..]),
+            None => (target, ""),
+        };
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| match pair.find('=') {
+                Some(offset) => (
+                    percent_decode(&pair[..offset].replace('+', " ")),
+                    percent_decode(&pair[offset + 1..].replace('+', " ")),
+                ),
+                None => (percent_decode(&pair.replace('+', " ")), String::new()),
+            })
+            .collect();
+        Ok(Some(Request {
+            method,
+            path: percent_decode(path),
+            query,
+            headers,
+        }))
+    }
+
+    fn route(request: &Request, context: &Context) -> Response {
+        if request.path.starts_with("/control/") {
+            return StatusServer::control(request, context);
+        }
+        if request.method != "GET" {
+            return Response::error(405, "only GET is supported");
+        }
+        match request.path.as_str() {
+            "/" => StatusServer::index(context),
+            "/status.json" => {
+                let disk_free = context
+                    .output
+                    .basepath()
+                    .ok()
+                    .and_then(|path| available_space(&path));
+                let json = match context.status.lock() {
+                    Ok(status) => status.to_json(disk_free),
+                    Err(_) => return Response::error(500, "status unavailable"),
+                };
+                Response::ok("application/json", json.into_bytes())
+            }
+            "/latest.png" => {
+                let latest = context
+                    .status
+                    .lock()
+                    .ok()
+                    .and_then(|status| status.last_frame.clone());
+                match latest.and_then(|path| fs::read(path).ok()) {
+                    Some(body) => Response::ok("image/png", body),
+                    None => Response::error(404, "no frames captured yet"),
+                }
+            }
+            "/gallery" => {
+                let page = request
+                    .query
+                    .get("page")
+                    .and_then(|p| str::parse::<usize>(p).ok())
+                    .unwrap_or(1)
+                    .max(1);
+                StatusServer::gallery(context, page)
+            }
+            path if path.starts_with("/frames/") => {
+                let name = Path::new(&path["/frames/".len()..]);
+                let safe = name.components().count() > 0
+                    && name.components().all(|c| match c {
+                        Component::Normal(part) => !part.to_string_lossy().starts_with('.'),
+                        _ => false,
+                    })
+                    && !name.to_string_lossy().contains('\\')
+                    && FolderLayout::is_layout_path(name);
+                if !safe {
+                    return Response::error(404, "not found");
+                }
+                match context
+                    .output
+                    .basepath()
+                    .ok()
+                    .and_then(|base| fs::read(base.join(name)).ok())
+                {
+                    Some(body) => Response::ok("image/png", body),
+                    None => Response::error(404, "not found"),
+                }
+            }
+            _ => Response::error(404, "not found"),
+        }
+    }
+
+    fn control(request: &Request, context: &Context) -> Response {
+        let control = match context.control.as_ref() {
+            Some(control) => control,
+            None => return Response::error(404, "remote control is not enabled"),
+        };
+        if request.method != "POST" {
+            return Response::error(405, "control requests must use POST");
+        }
+        if let Some(token) = context.token.as_ref() {
+            let expected = format!("Bearer {}", token);
+            if request.headers.get("authorization") != Some(&expected) {
+                return Response::error(401, "missing or invalid token");
+            }
+        }
+        let name = &request.path["/control/".len()..];
+        let command = if name == "interval" {
+            format!(
+                "interval:{}",
+                request.query.get("ms").cloned().unwrap_or_default()
+            )
+        } else {
+            name.to_string()
+        };
+        let command = match command.parse::<ControlCommand>() {
+            Ok(command) => command,
+            Err(err) => return Response::error(400, &err),
+        };
+        control.apply(command);
+        if let Ok(mut status) = context.status.lock() {
+            match command {
+                ControlCommand::Pause => status.paused = true,
+                ControlCommand::Resume => status.paused = false,
+                ControlCommand::SetInterval(interval) => status.interval = interval,
+                _ => {}
+            }
+        }
+        Response::ok("application/json", b"{\"ok\":true}".to_vec())
+    }
+
+    fn index(context: &Context) -> Response {
+        let status = match context.status.lock() {
+            Ok(status) => status.clone(),
+            Err(_) => return Response::error(500, "status unavailable"),
+        };
+        let last_error = status
+            .last_error
+            .as_ref()
+            .map(|e| format!("<p>Last error: {}</p>", html_escape(e)))
+            .unwrap_or_default();
+        let paused = if status.paused {
+            "<p>Capture is paused</p>"
+        } else {
+            ""
+        };
+        Response::html(format!(
+            "<!DOCTYPE html><html><head><title>snapshot</title>\
+             <meta http-equiv=\"refresh\" content=\"30\"></head><body>\
+             <h1>snapshot</h1>\
+             <p>{} frames since {}</p>{}{}\
+             <p><a href=\"/gallery\">gallery</a> | <a href=\"/status.json\">status</a></p>\
+             <img src=\"/latest.png\" style=\"max-width:100%\" alt=\"latest frame\">\
+             </body></html>",
+            status.frame_count,
+            status.started.to_rfc2822(),
+            paused,
+            last_error
+        ))
+    }
+
+    fn gallery(context: &Context, page: usize) -> Response {
+        let base = match context.output.basepath() {
+            Ok(base) => base,
+            Err(_) => return Response::error(500, "unable to read output folder"),
+        };
+        // Only walk as far back as this page, plus one frame to tell if there are older pages
+        let start = (page - 1) * context.page_size;
+        let frames: Result<Vec<PathBuf>, ResourceError> = match context.output.walk_newest() {
+            Ok(walk) => walk
+                .filter(|path| match path {
+                    Ok(path) => path.extension() == Some(OsStr::new("png")),
+                    Err(_) => true,
+                })
+                .skip(start)
+                .take(context.page_size + 1)
+                .collect(),
+            Err(err) => Err(err),
+        };
+        let frames = match frames {
+            Ok(frames) => frames,
+            Err(_) => return Response::error(500, "unable to read output folder"),
+        };
+
+        let items: String = frames
+            .iter()
+            .take(context.page_size)
+            .filter_map(|path| {
+                let name = path.file_name()?.to_string_lossy().to_string();
+                let relative = path.strip_prefix(&base).ok()?;
+                let url: Vec<String> = relative
+                    .components()
+                    .map(|c| percent_encode(&c.as_os_str().to_string_lossy()))
+                    .collect();
+                Some((name, url.join("/")))
+            })
+            .map(|(name, url)| {
+                let url = format!("/frames/{}", url);
+                format!(
+                    "<figure style=\"display:inline-block\"><a href=\"{0}\">\
+                     <img src=\"{0}\" width=\"240\" loading=\"lazy\"></a>\
+                     <figcaption>{1}</figcaption></figure>",
+                    url,
+                    html_escape(&name)
+                )
+            })
+            .collect();
+        let mut links = Vec::new();
+        if page > 1 {
+            links.push(format!("<a href=\"/gallery?page={}\">newer</a>", page - 1));
+        }
+        if frames.len() > context.page_size {
+            links.push(format!("<a href=\"/gallery?page={}\">older</a>", page + 1));
+        }
+        Response::html(format!(
+            "<!DOCTYPE html><html><head><title>snapshot gallery</title></head><body>\
+             <h1>Gallery</h1><p><a href=\"/\">latest</a> | page {} {}</p>\
+             <div>{}</div></body></html>",
+            page,
+            links.join(" "),
+            items
+        ))
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                output.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        output.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&output).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{percent_decode, percent_encode, StatusServer};
+    use crate::app::capture_status::CaptureStatus;
+    use crate::resources::{CaptureControl, ResourceFolder};
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::path::PathBuf;
+
+    fn get(port: u16, path: &str) -> (String, Vec<u8>) {
+        request(port, "GET", path, "")
+    }
+
+    fn request(port: u16, method: &str, path: &str, headers: &str) -> (String, Vec<u8>) {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n",
+            method, path, headers
+        )
+        .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        (
+            String::from_utf8_lossy(&response[..split]).to_string(),
+            response[split + 4..].to_vec(),
+        )
+    }
+
+    #[test]
+    pub fn test_percent_encoding() {
+        let name = "1583065800000-Sun, 01 Mar 2020 12:30:00 +0000.png";
+        assert_eq!(percent_decode(&percent_encode(name)), name);
+        assert_eq!(percent_decode("a+b%2Fc%2"), "a+b/c%2");
+    }
+
+    #[test]
+    pub fn test_serves_status_and_frames() {
+        let status = CaptureStatus::shared();
+        let output = ResourceFolder::new("test/data/frames")
+            .require_existing()
+            .unwrap();
+        let server =
+            StatusServer::start("127.0.0.1:0", status.clone(), output, 2, None, None).unwrap();
+
+        let (head, _) = get(server.port(), "/latest.png");
+        assert!(head.starts_with("HTTP/1.1 404"));
+
+        {
+            let mut status = status.lock().unwrap();
+            status.frame_count = 1;
+            status.last_frame = Some(PathBuf::from("test/data/frames/frame_00000000.png"));
+        }
+        let (head, body) = get(server.port(), "/latest.png");
+        assert!(head.starts_with("HTTP/1.1 200"));
+        assert_eq!(&body[1..4], b"PNG");
+
+        let (_, body) = get(server.port(), "/status.json");
+        assert!(String::from_utf8_lossy(&body).contains("\"frame_count\":1"));
+
+        let (_, body) = get(server.port(), "/gallery?page=2");
+        let body = String::from_utf8_lossy(&body).to_string();
+        assert!(body.contains("page 2 "));
+        assert!(body.contains("newer"));
+        assert!(body.contains("older"));
+        assert_eq!(body.matches("<figure").count(), 2);
+
+        let (head, _) = get(server.port(), "/frames/frame_00000001.png");
+        assert!(head.starts_with("HTTP/1.1 200"));
+        let (head, _) = get(server.port(), "/frames/..%2FCargo.toml");
+        assert!(head.starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    pub fn test_control_requests() {
+        let status = CaptureStatus::shared();
+        let control = CaptureControl::new();
+        let output = ResourceFolder::new("test/data/frames")
+            .require_existing()
+            .unwrap();
+        let server = StatusServer::start(
+            "127.0.0.1:0",
+            status.clone(),
+            output,
+            10,
+            Some(control.clone()),
+            Some("secret".to_string()),
+        )
+        .unwrap();
+        let auth = "Authorization: Bearer secret\r\n";
+
+        let (head, _) = request(server.port(), "POST", "/control/pause", "");
+        assert!(head.starts_with("HTTP/1.1 401"));
+        let (head, _) = request(server.port(), "GET", "/control/pause", auth);
+        assert!(head.starts_with("HTTP/1.1 405"));
+        let (head, _) = request(server.port(), "POST", "/control/pause", auth);
+        assert!(head.starts_with("HTTP/1.1 200"));
+        assert!(control.is_paused());
+
+        let (head, _) = request(server.port(), "POST", "/control/interval?ms=250", auth);
+        assert!(head.starts_with("HTTP/1.1 200"));
+        assert_eq!(control.interval(), Some(250));
+        let (head, _) = request(server.port(), "POST", "/control/interval?ms=x", auth);
+        assert!(head.starts_with("HTTP/1.1 400"));
+
+        let (_, body) = get(server.port(), "/status.json");
+        let body = String::from_utf8_lossy(&body).to_string();
+        assert!(body.contains("\"paused\":true"));
+        assert!(body.contains("\"interval\":250"));
+
+        request(server.port(), "POST", "/control/stop", auth);
+        assert!(control.is_stopped());
+    }
+
+    #[test]
+    pub fn test_control_needs_token_off_loopback() {
+        let output = ResourceFolder::new("test/data/frames")
+            .require_existing()
+            .unwrap();
+        let control = Some(CaptureControl::new());
+        let status = CaptureStatus::shared();
+        assert!(StatusServer::start(
+            "0.0.0.0:0",
+            status.clone(),
+            output.clone(),
+            10,
+            control.clone(),
+            None
+        )
+        .is_err());
+        assert!(StatusServer::start(
+            "0.0.0.0:0",
+            status.clone(),
+            output.clone(),
+            10,
+            control.clone(),
+            Some("secret".to_string())
+        )
+        .is_ok());
+        assert!(StatusServer::start("127.0.0.1:0", status, output, 10, control, None).is_ok());
+    }
+}
@@ -1,9 +1,33 @@
+mod bitmap_font;
+mod blending;
+mod contact_sheet;
+mod dedup;
+mod deflicker;
 mod ffmpeg_exporter;
+mod incremental;
+mod overlay;
+mod pipeline;
+mod rolling_preview;
+mod stabilize;
+mod staging;
+
+pub use self::bitmap_font::BitmapFont;
+pub use self::blending::{CrossFade, FrameAverage, FrameDuplicate};
+pub use self::contact_sheet::{ContactSheet, SheetFrame};
+pub use self::dedup::{DedupMode, DedupReport, Deduplicator, RemovedFrame};
+pub use self::deflicker::{Deflicker, SmoothingMethod};
+pub use self::incremental::{IncrementalExport, IncrementalState, Segment};
+pub use self::overlay::{parse_color, Overlay, OverlayPosition, OverlayStage};
+pub use self::pipeline::{ExportFrame, ExportPipeline, ExportStage};
+pub use self::rolling_preview::RollingPreview;
+pub use self::stabilize::{AlignReference, CropRect, Stabilizer};
+pub use self::staging::StagingFolder;
 
 use crate::encoding::error::EncodingError;
-use crate::encoding::ffmpeg_exporter::invoke_ffmpeg_cli;
+use crate::encoding::ffmpeg_exporter::{invoke_ffmpeg_cli, invoke_ffmpeg_concat};
 use crate::hardware::Frame;
 use crate::resources::ResourceFolder;
+use std::path::{Path, PathBuf};
 
 pub struct Encoding {}
 
@@ -49,12 +73,64 @@ impl Encoding {
         let folder = folder.basepath()?;
         invoke_ffmpeg_cli(&folder, pattern, output, framerate)
     }
+
+    /// Export only the given frames, in order, by running them through the pipeline into
+    /// a scratch folder first.
+    pub fn export_frames(
+        &self,
+        frames: &[PathBuf],
+        pipeline: &mut ExportPipeline,
+        staging: &Path,
+        output: &str,
+        framerate: u32,
+    ) -> Result<(), EncodingError> {
+        let mut staging = StagingFolder::new(staging)?;
+        pipeline.run(frames, &mut staging)?;
+        invoke_ffmpeg_cli(staging.path(), "*.png", output, framerate)?;
+        staging.remove()
+    }
+
+    /// Encode the new frames as one more segment, then join every segment into the output.
+    pub fn export_incremental(
+        &self,
+        frames: &[PathBuf],
+        pipeline: &mut ExportPipeline,
+        staging: &Path,
+        incremental: &mut IncrementalExport,
+        output: &str,
+        framerate: u32,
+    ) -> Result<(), EncodingError> {
+        let changed = !frames.is_empty();
+        if changed {
+            let segment = incremental.next_segment_path();
+            let mut staging = StagingFolder::new(staging)?;
+            pipeline.run(frames, &mut staging)?;
+            invoke_ffmpeg_cli(
+                staging.path(),
+                "*.png",
+                &segment.to_string_lossy(),
+                framerate,
+            )?;
+            staging.remove()?;
+            incremental.add_segment(&segment, frames);
+        }
+        incremental.finish()?;
+
+        let segments = incremental.segments();
+        if !segments.is_empty() && (changed || !Path::new(output).exists()) {
+            invoke_ffmpeg_concat(&segments, output)?;
+        }
+        Ok(())
+    }
 }
 
 pub mod error {
+    use crate::analysis::AnalysisError;
     use crate::resources::ResourceError;
+    use image::ImageError;
     use std::error::Error;
     use std::fmt;
+    use std::io;
 
     #[derive(Debug)]
     pub enum EncodingError {
@@ -62,6 +138,7 @@ pub mod error {
         InvalidBufferData,
         FailedToRenderVideo(String),
         InvalidSourceData(String),
+        InvalidSettings(String),
     }
 
     impl fmt::Display for EncodingError {
@@ -77,12 +154,32 @@ pub mod error {
             EncodingError::InvalidSourceData(format!("{}", err))
         }
     }
+
+    impl From<ImageError> for EncodingError {
+        fn from(err: ImageError) -> Self {
+            EncodingError::InvalidSourceData(format!("{}", err))
+        }
+    }
+
+    impl From<AnalysisError> for EncodingError {
+        fn from(err: AnalysisError) -> Self {
+            EncodingError::InvalidSettings(format!("{}", err))
+        }
+    }
+
+    impl From<io::Error> for EncodingError {
+        fn from(err: io::Error) -> Self {
+            EncodingError::InvalidSourceData(format!("{}", err))
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Encoding;
+    use super::{Encoding, ExportPipeline, IncrementalExport};
+    use crate::resources::ResourceFolder;
     use std::fs;
+    use std::path::{Path, PathBuf};
 
     #[test]
     pub fn test_save_as_rgb() {
@@ -94,4 +191,107 @@ mod test {
             .unwrap();
         buffer.save("test/data/image.png").unwrap();
     }
+
+    /// Point SNAPSHOT_FFMPEG at a stand in for ffmpeg, which writes the number of png files
+    /// in its working folder to its last argument, the output file
+    #[cfg(unix)]
+    fn use_fake_ffmpeg() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::sync::Once;
+
+        static SETUP: Once = Once::new();
+        SETUP.call_once(|| {
+            let folder = Path::new("test/output/fake_ffmpeg");
+            fs::create_dir_all(folder).unwrap();
+            let ffmpeg = folder.join("ffmpeg.sh");
+            fs::write(
+                &ffmpeg,
+                "#!/bin/sh\nfor last; do :; done\nls *.png 2>/dev/null | wc -l > \"$last\"\n",
+            )
+            .unwrap();
+            fs::set_permissions(&ffmpeg, fs::Permissions::from_mode(0o755)).unwrap();
+            std::env::set_var("SNAPSHOT_FFMPEG", fs::canonicalize(&ffmpeg).unwrap());
+        });
+    }
+
+    #[test]
+    #[cfg(unix)]
+    pub fn test_export_incremental_with_relative_paths() {
+        use_fake_ffmpeg();
+        let folder = Path::new("test/output/incremental_export");
+        let _ = fs::remove_dir_all(folder);
+        fs::create_dir_all(folder).unwrap();
+
+        let frames: Vec<PathBuf> = (0..3)
+            .map(|i| PathBuf::from(format!("test/data/frames/frame_{:08}.png", i)))
+            .collect();
+        let output = folder.join("out.webm").to_string_lossy().to_string();
+        let mut incremental = IncrementalExport::open(
+            &folder.join("out.webm.state"),
+            &folder.join("segments"),
+            "settings",
+        )
+        .unwrap();
+        Encoding::new()
+            .export_incremental(
+                &incremental.take_new_frames(&frames),
+                &mut ExportPipeline::new(),
+                &folder.join("staging"),
+                &mut incremental,
+                &output,
+                24,
+            )
+            .unwrap();
+
+        let segment = folder.join("segments/segment_00000.webm");
+        assert_eq!(fs::read_to_string(segment).unwrap().trim(), "3");
+        assert!(Path::new(&output).exists());
+        assert!(folder.join("out.webm.state").exists());
+        assert!(!folder.join("staging").exists());
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    pub fn test_export_frames_from_folder_layout() {
+        use_fake_ffmpeg();
+        let folder = Path::new("test/output/layout_export");
+        let _ = fs::remove_dir_all(folder);
+        for (i, subfolder) in ["2020/03/01/12", "2020/03/01/13", "2020/03/02/09"]
+            .iter()
+            .enumerate()
+        {
+            let subfolder = folder.join(subfolder);
+            fs::create_dir_all(&subfolder).unwrap();
+            fs::copy(
+                "test/data/frames/frame_00000000.png",
+                subfolder.join(format!("158306580{}000-frame.png", i)),
+            )
+            .unwrap();
+        }
+
+        let input = ResourceFolder::new(&folder.to_string_lossy())
+            .require_existing()
+            .unwrap();
+        let frames: Vec<PathBuf> = input
+            .walk_files()
+            .unwrap()
+            .into_iter()
+            .filter(|path| path.extension().map(|e| e == "png") == Some(true))
+            .collect();
+        assert_eq!(frames.len(), 3);
+
+        let output = fs::canonicalize(folder).unwrap().join("out.webm");
+        Encoding::new()
+            .export_frames(
+                &frames,
+                &mut ExportPipeline::new(),
+                &folder.join("staging"),
+                &output.to_string_lossy(),
+                24,
+            )
+            .unwrap();
+        assert_eq!(fs::read_to_string(&output).unwrap().trim(), "3");
+        fs::remove_dir_all(folder).unwrap();
+    }
 }
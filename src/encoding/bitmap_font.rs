@@ -0,0 +1,152 @@
+use image::{Rgb, RgbImage};
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// A tiny fixed width 5x7 font so text can be burned into frames without a font file.
+/// Each glyph is 7 rows, with the low 5 bits of each row as pixels (msb on the left).
+/// Only upper case letters are included; lower case letters are drawn as upper case.
+pub struct BitmapFont {
+    scale: u32,
+}
+
+impl Default for BitmapFont {
+    fn default() -> Self {
+        BitmapFont { scale: 1 }
+    }
+}
+
+impl BitmapFont {
+    pub fn new() -> BitmapFont {
+        Default::default()
+    }
+
+    /// Each font pixel is drawn as a scale x scale block
+    pub fn with_scale(scale: u32) -> BitmapFont {
+        BitmapFont {
+            scale: scale.max(1),
+        }
+    }
+
+    /// The size in pixels of the rendered text, including a one pixel gap between glyphs
+    pub fn measure(&self, text: &str) -> (u32, u32) {
+        let count = text.chars().count() as u32;
+        if count == 0 {
+            return (0, 0);
+        }
+        (
+            (count * (GLYPH_WIDTH + 1) - 1) * self.scale,
+            GLYPH_HEIGHT * self.scale,
+        )
+    }
+
+    /// Draw the text with its top left corner at x, y; anything outside the image is clipped.
+    pub fn draw(&self, image: &mut RgbImage, text: &str, x: i64, y: i64, color: Rgb<u8>) {
+        let (width, height) = (image.width() as i64, image.height() as i64);
+        let scale = self.scale as i64;
+        for (index, c) in text.chars().enumerate() {
+            let origin_x = x + index as i64 * (GLYPH_WIDTH as i64 + 1) * scale;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH as i64 {
+                    if bits & (1 << (GLYPH_WIDTH as i64 - 1 - col)) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let px = origin_x + col * scale + dx;
+                            let py = y + row as i64 * scale + dy;
+                            if px >= 0 && py >= 0 && px < width && py < height {
+                                image.put_pixel(px as u32, py as u32, color);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+        '1' => [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        '2' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+        '3' => [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+        '4' => [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+        '5' => [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+        '6' => [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+        '7' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+        '9' => [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+        ':' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+        '-' => [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '#' => [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08],
+        ';' => [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        '\'' => [0x0c, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '"' => [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f],
+        '=' => [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00],
+        '*' => [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x00, 0x00, 0x04],
+        '?' => [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        '&' => [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d],
+        '@' => [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e],
+        'A' => [0x0e, 0x11, 0x11, 0x11, 0x1f, 0x11, 0x11],
+        'B' => [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+        'C' => [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+        'D' => [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+        'E' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+        'F' => [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+        'G' => [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+        'H' => [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+        'I' => [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+        'M' => [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'P' => [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+        'Q' => [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+        'R' => [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+        'S' => [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+        'T' => [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+        'X' => [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+        'Z' => [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+        _ => [0x00; 7],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitmapFont;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    pub fn test_measure() {
+        assert_eq!(BitmapFont::new().measure("12:00"), (29, 7));
+        assert_eq!(BitmapFont::with_scale(2).measure("1"), (10, 14));
+        assert_eq!(BitmapFont::new().measure(""), (0, 0));
+    }
+
+    #[test]
+    pub fn test_draw_clips_to_image() {
+        let mut image = RgbImage::new(8, 8);
+        let font = BitmapFont::with_scale(3);
+        font.draw(&mut image, "8-8", -4, 2, Rgb([255, 255, 255]));
+        assert!(image.pixels().any(|p| p[0] == 255));
+    }
+}
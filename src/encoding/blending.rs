@@ -0,0 +1,172 @@
+use crate::encoding::error::EncodingError;
+use crate::encoding::pipeline::{ExportFrame, ExportStage};
+use image::RgbImage;
+use std::collections::VecDeque;
+
+/// Inserts frames fading from each frame to the next, to smooth out sparse sequences.
+pub struct CrossFade {
+    steps: u32,
+    previous: Option<ExportFrame>,
+}
+
+impl CrossFade {
+    /// `steps` is the number of blended frames inserted between each pair of frames
+    pub fn new(steps: u32) -> CrossFade {
+        CrossFade {
+            steps,
+            previous: None,
+        }
+    }
+}
+
+impl ExportStage for CrossFade {
+    fn process(&mut self, frame: ExportFrame) -> Result<Vec<ExportFrame>, EncodingError> {
+        let previous = match self.previous.replace(frame) {
+            Some(p) => p,
+            None => return Ok(Vec::new()),
+        };
+        let mut output = Vec::new();
+        if let Some(next) = self.previous.as_ref() {
+            if next.image.dimensions() == previous.image.dimensions() {
+                for step in 1..=self.steps {
+                    let t = step as f32 / (self.steps + 1) as f32;
+                    output.push(ExportFrame {
+                        image: blend(&previous.image, &next.image, t),
+                        index: previous.index,
+                    });
+                }
+            }
+        }
+        output.insert(0, previous);
+        Ok(output)
+    }
+
+    fn flush(&mut self) -> Result<Vec<ExportFrame>, EncodingError> {
+        Ok(self.previous.take().into_iter().collect())
+    }
+}
+
+/// Replaces each frame with the average of it and the frames before it, like a long
+/// exposure; moving objects blur out and noise is reduced.
+pub struct FrameAverage {
+    window: usize,
+    frames: VecDeque<RgbImage>,
+}
+
+impl FrameAverage {
+    pub fn new(window: usize) -> FrameAverage {
+        FrameAverage {
+            window: window.max(1),
+            frames: VecDeque::new(),
+        }
+    }
+}
+
+impl ExportStage for FrameAverage {
+    fn process(&mut self, frame: ExportFrame) -> Result<Vec<ExportFrame>, EncodingError> {
+        if let Some(last) = self.frames.back() {
+            if last.dimensions() != frame.image.dimensions() {
+                self.frames.clear();
+            }
+        }
+        self.frames.push_back(frame.image);
+        while self.frames.len() > self.window {
+            self.frames.pop_front();
+        }
+
+        let (width, height) = self.frames[0].dimensions();
+        let mut sums = vec![0u32; (width * height * 3) as usize];
+        for image in self.frames.iter() {
+            for (sum, value) in sums.iter_mut().zip(image.as_raw().iter()) {
+                *sum += *value as u32;
+            }
+        }
+        let count = self.frames.len() as u32;
+        let raw = sums.iter().map(|sum| (sum / count) as u8).collect();
+        match RgbImage::from_raw(width, height, raw) {
+            Some(image) => Ok(vec![ExportFrame {
+                image,
+                index: frame.index,
+            }]),
+            None => Err(EncodingError::InvalidBufferData),
+        }
+    }
+}
+
+/// Repeats each frame, to slow the sequence down without changing the framerate.
+pub struct FrameDuplicate {
+    count: u32,
+}
+
+impl FrameDuplicate {
+    pub fn new(count: u32) -> FrameDuplicate {
+        FrameDuplicate {
+            count: count.max(1),
+        }
+    }
+}
+
+impl ExportStage for FrameDuplicate {
+    fn process(&mut self, frame: ExportFrame) -> Result<Vec<ExportFrame>, EncodingError> {
+        let mut output = Vec::new();
+        for _ in 1..self.count {
+            output.push(ExportFrame {
+                image: frame.image.clone(),
+                index: frame.index,
+            });
+        }
+        output.push(frame);
+        Ok(output)
+    }
+}
+
+/// Linear blend from a (t = 0) to b (t = 1)
+fn blend(a: &RgbImage, b: &RgbImage, t: f32) -> RgbImage {
+    let raw = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw().iter())
+        .map(|(x, y)| (*x as f32 * (1.0 - t) + *y as f32 * t).round() as u8)
+        .collect();
+    RgbImage::from_raw(a.width(), a.height(), raw).unwrap_or_else(|| a.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrossFade, FrameAverage, FrameDuplicate};
+    use crate::encoding::pipeline::{ExportFrame, ExportStage};
+    use image::{Rgb, RgbImage};
+
+    fn frame(value: u8, index: usize) -> ExportFrame {
+        ExportFrame {
+            image: RgbImage::from_pixel(4, 4, Rgb([value, value, value])),
+            index,
+        }
+    }
+
+    fn values(frames: Vec<ExportFrame>) -> Vec<u8> {
+        frames.iter().map(|f| f.image.get_pixel(0, 0)[0]).collect()
+    }
+
+    #[test]
+    pub fn test_cross_fade() {
+        let mut stage = CrossFade::new(3);
+        assert!(stage.process(frame(0, 0)).unwrap().is_empty());
+        assert_eq!(
+            values(stage.process(frame(100, 1)).unwrap()),
+            vec![0, 25, 50, 75]
+        );
+        assert_eq!(values(stage.flush().unwrap()), vec![100]);
+    }
+
+    #[test]
+    pub fn test_average_and_duplicate() {
+        let mut stage = FrameAverage::new(2);
+        assert_eq!(values(stage.process(frame(10, 0)).unwrap()), vec![10]);
+        assert_eq!(values(stage.process(frame(30, 1)).unwrap()), vec![20]);
+        assert_eq!(values(stage.process(frame(50, 2)).unwrap()), vec![40]);
+
+        let mut stage = FrameDuplicate::new(3);
+        assert_eq!(values(stage.process(frame(7, 0)).unwrap()), vec![7, 7, 7]);
+    }
+}
@@ -0,0 +1,234 @@
+use crate::encoding::bitmap_font::{BitmapFont, GLYPH_HEIGHT};
+use crate::encoding::error::EncodingError;
+use crate::resources::TimeSnapshot;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use image::imageops::FilterType;
+use image::{Rgb, RgbImage};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// A frame on a contact sheet, with the capture time from its filename
+#[derive(Debug, Clone)]
+pub struct SheetFrame {
+    pub path: PathBuf,
+    pub time: DateTime<Utc>,
+}
+
+impl SheetFrame {
+    /// The frames with a capture time in their filename, in capture order
+    pub fn from_paths(paths: &[PathBuf]) -> Vec<SheetFrame> {
+        let mut frames: Vec<SheetFrame> = paths
+            .iter()
+            .filter_map(|path| {
+                TimeSnapshot::from_frame_path(path).map(|t| SheetFrame {
+                    path: path.clone(),
+                    time: t.utc,
+                })
+            })
+            .collect();
+        frames.sort_by_key(|frame| frame.time);
+        frames
+    }
+
+    /// The local date the frame was captured on
+    pub fn date(&self) -> NaiveDate {
+        self.time.with_timezone(&Local).naive_local().date()
+    }
+}
+
+/// Tiles downsized frames in a grid with a caption under each, for reviewing a day at a glance.
+pub struct ContactSheet {
+    columns: u32,
+    thumbnail_width: u32,
+    max_frames: usize,
+    caption_format: String,
+    background: Rgb<u8>,
+    color: Rgb<u8>,
+}
+
+impl ContactSheet {
+    pub fn new(columns: u32, thumbnail_width: u32) -> ContactSheet {
+        ContactSheet {
+            columns: columns.max(1),
+            thumbnail_width: thumbnail_width.max(16),
+            max_frames: 0,
+            caption_format: "%H:%M".to_string(),
+            background: Rgb([24, 24, 24]),
+            color: Rgb([255, 255, 255]),
+        }
+    }
+
+    /// Show at most this many frames, evenly spaced through the sequence; 0 for all frames
+    pub fn with_max_frames(mut self, max_frames: usize) -> ContactSheet {
+        self.max_frames = max_frames;
+        self
+    }
+
+    /// The chrono format string for the local capture time under each frame
+    pub fn with_caption_format(mut self, caption_format: &str) -> ContactSheet {
+        self.caption_format = caption_format.to_string();
+        self
+    }
+
+    /// Split the frames into one group per local date
+    pub fn group_by_day(frames: Vec<SheetFrame>) -> BTreeMap<NaiveDate, Vec<SheetFrame>> {
+        let mut days: BTreeMap<NaiveDate, Vec<SheetFrame>> = BTreeMap::new();
+        for frame in frames.into_iter() {
+            days.entry(frame.date()).or_default().push(frame);
+        }
+        days
+    }
+
+    /// The frames which will be shown on the sheet
+    pub fn select<'a>(&self, frames: &'a [SheetFrame]) -> Vec<&'a SheetFrame> {
+        if self.max_frames == 0 || frames.len() <= self.max_frames {
+            return frames.iter().collect();
+        }
+        let step = frames.len() as f32 / self.max_frames as f32;
+        (0..self.max_frames)
+            .map(|i| &frames[(i as f32 * step) as usize])
+            .collect()
+    }
+
+    pub fn render(&self, title: &str, frames: &[SheetFrame]) -> Result<RgbImage, EncodingError> {
+        if StrftimeItems::new(&self.caption_format).any(|item| item == Item::Error) {
+            return Err(EncodingError::InvalidSettings(format!(
+                "{} is not a valid caption format",
+                self.caption_format
+            )));
+        }
+        let selected = self.select(frames);
+        let first = match selected.first() {
+            Some(f) => image::open(&f.path)?.to_rgb8(),
+            None => {
+                return Err(EncodingError::InvalidSourceData(
+                    "no frames for contact sheet".to_string(),
+                ))
+            }
+        };
+
+        let margin = 8;
+        let caption_height = GLYPH_HEIGHT + 6;
+        let title_font = BitmapFont::with_scale(2);
+        let title_height = title_font.measure(title).1 + margin * 2;
+        let cell_width = self.thumbnail_width;
+        let cell_height = (cell_width * first.height() / first.width().max(1)).max(1);
+        let rows = (selected.len() as u32).div_ceil(self.columns);
+        let width = margin + self.columns * (cell_width + margin);
+        let height = title_height + rows * (cell_height + caption_height + margin);
+
+        let mut sheet = RgbImage::from_pixel(width, height, self.background);
+        title_font.draw(&mut sheet, title, margin as i64, margin as i64, self.color);
+
+        let font = BitmapFont::new();
+        for (index, frame) in selected.iter().enumerate() {
+            let image = if index == 0 {
+                first.clone()
+            } else {
+                image::open(&frame.path)?.to_rgb8()
+            };
+            let thumbnail = fit(&image, cell_width, cell_height);
+            let (column, row) = (index as u32 % self.columns, index as u32 / self.columns);
+            let x = margin + column * (cell_width + margin);
+            let y = title_height + row * (cell_height + caption_height + margin);
+            image::imageops::replace(
+                &mut sheet,
+                &thumbnail,
+                x + (cell_width - thumbnail.width()) / 2,
+                y + (cell_height - thumbnail.height()) / 2,
+            );
+
+            let caption = frame
+                .time
+                .with_timezone(&Local)
+                .format(&self.caption_format)
+                .to_string();
+            font.draw(
+                &mut sheet,
+                &caption,
+                x as i64,
+                (y + cell_height + 3) as i64,
+                self.color,
+            );
+        }
+        Ok(sheet)
+    }
+}
+
+/// Resize to fit inside width x height, keeping the aspect ratio
+fn fit(image: &RgbImage, width: u32, height: u32) -> RgbImage {
+    let scale = (width as f32 / image.width() as f32).min(height as f32 / image.height() as f32);
+    let w = ((image.width() as f32 * scale).round() as u32)
+        .max(1)
+        .min(width);
+    let h = ((image.height() as f32 * scale).round() as u32)
+        .max(1)
+        .min(height);
+    image::imageops::resize(image, w, h, FilterType::Triangle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContactSheet, SheetFrame};
+    use crate::encoding::error::EncodingError;
+    use crate::resources::TimeSnapshot;
+    use std::path::PathBuf;
+
+    fn frames() -> Vec<PathBuf> {
+        (0..3)
+            .map(|i| PathBuf::from(format!("test/data/frames/frame_0000000{}.png", i)))
+            .collect()
+    }
+
+    #[test]
+    pub fn test_group_and_select() {
+        let paths: Vec<PathBuf> = vec![
+            "1583065800000-a.png",
+            "1583065860000-b.png",
+            "1583152200000-c.png",
+            "notes.png",
+        ]
+        .into_iter()
+        .map(PathBuf::from)
+        .collect();
+        let frames = SheetFrame::from_paths(&paths);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(ContactSheet::group_by_day(frames.clone()).len(), 2);
+        let sheet = ContactSheet::new(4, 64).with_max_frames(2);
+        assert_eq!(sheet.select(&frames).len(), 2);
+    }
+
+    #[test]
+    pub fn test_render_grid() {
+        let frames: Vec<SheetFrame> = frames()
+            .into_iter()
+            .enumerate()
+            .map(|(i, path)| SheetFrame {
+                path,
+                time: TimeSnapshot::from_timestamp(i as u128 * 60_000).utc,
+            })
+            .collect();
+        let sheet = ContactSheet::new(2, 64)
+            .render("2020-03-01", &frames)
+            .unwrap();
+        // 2 columns of 64px with 8px margins; 2 rows
+        assert_eq!(sheet.width(), 8 + 2 * (64 + 8));
+        assert!(sheet.height() > 2 * 64 / 2);
+    }
+
+    #[test]
+    pub fn test_invalid_caption_format() {
+        let frames: Vec<SheetFrame> = frames()
+            .into_iter()
+            .map(|path| SheetFrame {
+                path,
+                time: TimeSnapshot::from_timestamp(0).utc,
+            })
+            .collect();
+        let result = ContactSheet::new(2, 64)
+            .with_caption_format("%Q")
+            .render("2020-03-01", &frames);
+        assert!(matches!(result, Err(EncodingError::InvalidSettings(_))));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::analysis::PerceptualHash;
+use crate::encoding::error::EncodingError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// What to do with a run of near identical frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DedupMode {
+    /// Keep only the first frame of the run
+    Drop,
+    /// Keep at most this many frames of the run, evenly spaced
+    Collapse(usize),
+}
+
+impl FromStr for DedupMode {
+    type Err = EncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if s == "drop" {
+            return Ok(DedupMode::Drop);
+        }
+        if s == "collapse" {
+            return Ok(DedupMode::Collapse(2));
+        }
+        if let Some(count) = s.strip_prefix("collapse:") {
+            if let Ok(count) = str::parse::<usize>(count) {
+                return Ok(DedupMode::Collapse(count.max(1)));
+            }
+        }
+        Err(EncodingError::InvalidSettings(format!(
+            "{} is not a valid dedup mode; use drop, collapse or collapse:N",
+            s
+        )))
+    }
+}
+
+#[derive(Debug)]
+pub struct RemovedFrame {
+    pub path: PathBuf,
+
+    /// The first frame of the run this frame was similar to
+    pub similar_to: PathBuf,
+
+    /// Hamming distance between the perceptual hashes of the two frames
+    pub distance: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct DedupReport {
+    pub kept: Vec<PathBuf>,
+    pub removed: Vec<RemovedFrame>,
+}
+
+impl DedupReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "kept {} of {} frames; removed {} near duplicates",
+            self.kept.len(),
+            self.kept.len() + self.removed.len(),
+            self.removed.len()
+        )
+    }
+
+    /// Write the summary and a line for each removed frame
+    pub fn save(&self, path: &Path) -> Result<(), EncodingError> {
+        let mut report = format!("{}\n", self.summary());
+        for removed in self.removed.iter() {
+            report.push_str(&format!(
+                "{}\tsimilar to {}\tdistance {}\n",
+                removed.path.display(),
+                removed.similar_to.display(),
+                removed.distance
+            ));
+        }
+        fs::write(path, report)?;
+        Ok(())
+    }
+}
+
+/// Drops runs of frames whose perceptual hashes are within `threshold` bits of each other.
+pub struct Deduplicator {
+    threshold: u32,
+    mode: DedupMode,
+}
+
+impl Deduplicator {
+    pub fn new(threshold: u32, mode: DedupMode) -> Deduplicator {
+        Deduplicator { threshold, mode }
+    }
+
+    /// Frames must be in capture order; each is compared to the first frame of the current run.
+    pub fn dedup(&self, frames: &[PathBuf]) -> Result<DedupReport, EncodingError> {
+        let mut report = DedupReport::default();
+        let mut run: Vec<(PathBuf, u32)> = Vec::new();
+        let mut anchor: Option<PerceptualHash> = None;
+        for frame in frames.iter() {
+            let hash = PerceptualHash::from_image(&image::open(frame)?.to_rgb8());
+            let distance = anchor.map(|a| a.distance(&hash));
+            match distance {
+                Some(d) if d <= self.threshold => run.push((frame.clone(), d)),
+                _ => {
+                    self.close_run(&mut run, &mut report);
+                    anchor = Some(hash);
+                    run.push((frame.clone(), 0));
+                }
+            }
+        }
+        self.close_run(&mut run, &mut report);
+        Ok(report)
+    }
+
+    fn close_run(&self, run: &mut Vec<(PathBuf, u32)>, report: &mut DedupReport) {
+        let keep = match self.mode {
+            DedupMode::Drop => 1,
+            DedupMode::Collapse(count) => count,
+        };
+        let first = match run.first() {
+            Some((path, _)) => path.clone(),
+            None => return,
+        };
+        let len = run.len();
+        let kept: Vec<usize> = if len <= keep {
+            (0..len).collect()
+        } else if keep == 1 {
+            vec![0]
+        } else {
+            (0..keep).map(|i| i * (len - 1) / (keep - 1)).collect()
+        };
+        for (index, (path, distance)) in run.drain(..).enumerate() {
+            if kept.contains(&index) {
+                report.kept.push(path);
+            } else {
+                report.removed.push(RemovedFrame {
+                    path,
+                    similar_to: first.clone(),
+                    distance,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DedupMode, Deduplicator};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    fn frames() -> Vec<PathBuf> {
+        let first = PathBuf::from("test/data/frames/frame_00000000.png");
+        let mut frames = vec![first.clone(), first.clone(), first.clone(), first];
+        frames.push(PathBuf::from("test/data/frames/frame_00000008.png"));
+        frames
+    }
+
+    #[test]
+    pub fn test_drop_duplicates() {
+        let report = Deduplicator::new(2, DedupMode::Drop)
+            .dedup(&frames())
+            .unwrap();
+        assert_eq!(report.removed.len(), 3);
+        assert_eq!(report.kept.len(), 2);
+        assert_eq!(report.removed[0].distance, 0);
+    }
+
+    #[test]
+    pub fn test_collapse_duplicates() {
+        let report = Deduplicator::new(2, DedupMode::from_str("collapse:2").unwrap())
+            .dedup(&frames())
+            .unwrap();
+        assert_eq!(report.kept.len(), 3);
+        assert!(DedupMode::from_str("merge").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::encoding::error::EncodingError;
+use crate::encoding::pipeline::{ExportFrame, ExportStage};
+use image::imageops::FilterType;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How the per frame colour statistics are smoothed across the sequence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SmoothingMethod {
+    Mean,
+    Median,
+}
+
+impl FromStr for SmoothingMethod {
+    type Err = EncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mean" => Ok(SmoothingMethod::Mean),
+            "median" => Ok(SmoothingMethod::Median),
+            _ => Err(EncodingError::InvalidSettings(format!(
+                "{} is not a valid deflicker method; use mean or median",
+                s
+            ))),
+        }
+    }
+}
+
+/// Removes exposure and white balance flicker by scaling each channel of every frame
+/// towards a rolling average of the channel means of the frames around it.
+pub struct Deflicker {
+    window: usize,
+    method: SmoothingMethod,
+    max_gain: f32,
+    gains: Vec<[f32; 3]>,
+}
+
+impl Deflicker {
+    /// `window` is the number of frames either side of each frame to smooth over
+    pub fn new(window: usize, method: SmoothingMethod) -> Deflicker {
+        Deflicker {
+            window: window.max(1),
+            method,
+            max_gain: 2.0,
+            gains: Vec::new(),
+        }
+    }
+
+    /// The mean of each channel, measured on a thumbnail
+    fn channel_means(path: &Path) -> Result<[f32; 3], EncodingError> {
+        let image = image::open(path)?.to_rgb8();
+        let thumbnail = image::imageops::resize(&image, 64, 48, FilterType::Triangle);
+        let mut sums = [0f64; 3];
+        for pixel in thumbnail.pixels() {
+            for c in 0..3 {
+                sums[c] += pixel[c] as f64;
+            }
+        }
+        let count = (thumbnail.width() * thumbnail.height()).max(1) as f64;
+        Ok([
+            (sums[0] / count) as f32,
+            (sums[1] / count) as f32,
+            (sums[2] / count) as f32,
+        ])
+    }
+
+    /// The gain to apply to each channel of each frame, given the channel means of every frame
+    pub fn gains_for(&self, means: &[[f32; 3]]) -> Vec<[f32; 3]> {
+        (0..means.len())
+            .map(|index| {
+                let start = index.saturating_sub(self.window);
+                let end = (index + self.window + 1).min(means.len());
+                let mut gains = [1f32; 3];
+                for (c, gain) in gains.iter_mut().enumerate() {
+                    let mut values: Vec<f32> = means[start..end].iter().map(|m| m[c]).collect();
+                    let target = match self.method {
+                        SmoothingMethod::Mean => values.iter().sum::<f32>() / values.len() as f32,
+                        SmoothingMethod::Median => {
+                            values.sort_by(|a, b| {
+                                a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+                            });
+                            values[values.len() / 2]
+                        }
+                    };
+                    let actual = means[index][c];
+                    if actual > 0.5 {
+                        *gain = (target / actual)
+                            .max(1.0 / self.max_gain)
+                            .min(self.max_gain);
+                    }
+                }
+                gains
+            })
+            .collect()
+    }
+}
+
+impl ExportStage for Deflicker {
+    fn prepare(&mut self, frames: &[PathBuf]) -> Result<(), EncodingError> {
+        let means = frames
+            .iter()
+            .map(|path| Deflicker::channel_means(path))
+            .collect::<Result<Vec<[f32; 3]>, EncodingError>>()?;
+        self.gains = self.gains_for(&means);
+        Ok(())
+    }
+
+    fn process(&mut self, mut frame: ExportFrame) -> Result<Vec<ExportFrame>, EncodingError> {
+        let gains = match self.gains.get(frame.index) {
+            Some(g) => *g,
+            None => return Ok(vec![frame]),
+        };
+        let mut tables = [[0u8; 256]; 3];
+        for (c, table) in tables.iter_mut().enumerate() {
+            for (value, entry) in table.iter_mut().enumerate() {
+                *entry = (value as f32 * gains[c]).round().min(255.0) as u8;
+            }
+        }
+        for pixel in frame.image.pixels_mut() {
+            for c in 0..3 {
+                pixel[c] = tables[c][pixel[c] as usize];
+            }
+        }
+        Ok(vec![frame])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Deflicker, SmoothingMethod};
+
+    #[test]
+    pub fn test_smooths_flicker() {
+        let means = vec![
+            [100.0, 100.0, 100.0],
+            [100.0, 100.0, 100.0],
+            [150.0, 120.0, 100.0],
+            [100.0, 100.0, 100.0],
+            [100.0, 100.0, 100.0],
+        ];
+        let gains = Deflicker::new(2, SmoothingMethod::Median).gains_for(&means);
+        assert_eq!(gains[0], [1.0, 1.0, 1.0]);
+        assert!((gains[2][0] - 100.0 / 150.0).abs() < 0.001);
+        assert!((gains[2][1] - 100.0 / 120.0).abs() < 0.001);
+        assert_eq!(gains[2][2], 1.0);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::encoding::error::EncodingError;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 /// Try to export the frames from input_folder as the given output path.
@@ -11,13 +12,7 @@ pub fn invoke_ffmpeg_cli(
     output_file: &str,
     framerate: u32,
 ) -> Result<(), EncodingError> {
-    let mut cmd = if cfg!(target_os = "windows") {
-        Command::new("ffmpeg.exe")
-    } else {
-        Command::new("ffmpeg")
-    };
-
-    let r = cmd
+    let r = ffmpeg_command()
         .args(&[
             "-y",
             "-framerate",
@@ -43,8 +38,65 @@ pub fn invoke_ffmpeg_cli(
     match r {
         Ok(result) => {
             println!("video encoding status: {}", result.status);
-            Ok(())
+            if result.status.success() {
+                Ok(())
+            } else {
+                Err(EncodingError::FailedToRenderVideo(format!(
+                    "ffmpeg failed: {}",
+                    result.status
+                )))
+            }
         }
         Err(err) => Err(EncodingError::FailedToRenderVideo(format!("{}", err))),
     }
 }
+
+/// Join the videos into output_file without re-encoding them, using the ffmpeg concat demuxer.
+/// The videos must all have been encoded with the same settings.
+pub fn invoke_ffmpeg_concat(videos: &[PathBuf], output_file: &str) -> Result<(), EncodingError> {
+    let list_path = PathBuf::from(format!("{}.concat.txt", output_file));
+    let list: String = videos
+        .iter()
+        .map(fs::canonicalize)
+        .collect::<Result<Vec<PathBuf>, _>>()?
+        .iter()
+        .map(|video| {
+            format!(
+                "file '{}'\n",
+                video.display().to_string().replace('\'', "'\\''")
+            )
+        })
+        .collect();
+    fs::write(&list_path, list)?;
+
+    let r = ffmpeg_command()
+        .args(["-y", "-f", "concat", "-safe", "0", "-i"])
+        .arg(&list_path)
+        .args(["-c", "copy", output_file])
+        .stdin(Stdio::null())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output();
+    fs::remove_file(&list_path)?;
+
+    match r {
+        Ok(result) if result.status.success() => Ok(()),
+        Ok(result) => Err(EncodingError::FailedToRenderVideo(format!(
+            "concat failed: {}",
+            result.status
+        ))),
+        Err(err) => Err(EncodingError::FailedToRenderVideo(format!("{}", err))),
+    }
+}
+
+/// The ffmpeg on the path, or the one named by SNAPSHOT_FFMPEG
+fn ffmpeg_command() -> Command {
+    if let Some(path) = std::env::var_os("SNAPSHOT_FFMPEG") {
+        return Command::new(path);
+    }
+    if cfg!(target_os = "windows") {
+        Command::new("ffmpeg.exe")
+    } else {
+        Command::new("ffmpeg")
+    }
+}
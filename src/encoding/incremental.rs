@@ -0,0 +1,196 @@
+use crate::encoding::error::EncodingError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A video file holding a run of frames which have already been encoded
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Segment {
+    pub file: String,
+    pub frames: usize,
+    pub first_frame: String,
+    pub last_frame: String,
+}
+
+/// What has been encoded so far, saved between runs of assemble
+#[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct IncrementalState {
+    /// A hash of the export settings; if they change every frame is encoded again
+    pub fingerprint: String,
+
+    /// The filename of the last frame that has been encoded
+    pub last_frame: Option<String>,
+
+    pub segments: Vec<Segment>,
+}
+
+/// Tracks which frames have been encoded, so that each run only encodes new frames into a
+/// new segment, which is then concatenated with the earlier segments without re-encoding.
+pub struct IncrementalExport {
+    state_path: PathBuf,
+    segment_folder: PathBuf,
+    state: IncrementalState,
+    pending_last_frame: Option<String>,
+}
+
+impl IncrementalExport {
+    /// Load the saved state; if the fingerprint does not match, the old segments are deleted
+    /// and everything will be encoded again
+    pub fn open(
+        state_path: &Path,
+        segment_folder: &Path,
+        fingerprint: &str,
+    ) -> Result<IncrementalExport, EncodingError> {
+        // ffmpeg runs in the staging folder, so relative paths would resolve against that
+        fs::create_dir_all(segment_folder)?;
+        let segment_folder = fs::canonicalize(segment_folder)?;
+        let state_path = absolute_path(state_path)?;
+        let mut export = IncrementalExport {
+            state_path: state_path.clone(),
+            segment_folder,
+            state: Default::default(),
+            pending_last_frame: None,
+        };
+        if state_path.exists() {
+            let raw = fs::read_to_string(&state_path)?;
+            export.state = toml::from_str(&raw).map_err(|err| {
+                EncodingError::InvalidSourceData(format!("invalid state file: {}", err))
+            })?;
+        }
+        if export.state.fingerprint != fingerprint {
+            for segment in export.segments() {
+                if segment.exists() {
+                    fs::remove_file(segment)?;
+                }
+            }
+            export.state = IncrementalState {
+                fingerprint: fingerprint.to_string(),
+                ..Default::default()
+            };
+        }
+        Ok(export)
+    }
+
+    pub fn state(&self) -> &IncrementalState {
+        &self.state
+    }
+
+    /// The frames captured after the last encoded frame; frames must be in capture order.
+    /// The last of them is marked as encoded by finish(), even if it is later dropped (eg.
+    /// by dedup) and not encoded.
+    pub fn take_new_frames(&mut self, frames: &[PathBuf]) -> Vec<PathBuf> {
+        let new_frames: Vec<PathBuf> = match self.state.last_frame.as_ref() {
+            Some(last) => frames
+                .iter()
+                .filter(|path| file_name(path).as_str() > last.as_str())
+                .cloned()
+                .collect(),
+            None => frames.to_vec(),
+        };
+        self.pending_last_frame = new_frames.last().map(|path| file_name(path));
+        new_frames
+    }
+
+    /// Where the next segment should be encoded to
+    pub fn next_segment_path(&self) -> PathBuf {
+        self.segment_folder
+            .join(format!("segment_{:05}.webm", self.state.segments.len()))
+    }
+
+    /// Every segment, in order
+    pub fn segments(&self) -> Vec<PathBuf> {
+        self.state
+            .segments
+            .iter()
+            .map(|segment| self.segment_folder.join(&segment.file))
+            .collect()
+    }
+
+    /// Record an encoded segment
+    pub fn add_segment(&mut self, path: &Path, frames: &[PathBuf]) {
+        if let (Some(first), Some(last)) = (frames.first(), frames.last()) {
+            self.state.segments.push(Segment {
+                file: file_name(path),
+                frames: frames.len(),
+                first_frame: file_name(first),
+                last_frame: file_name(last),
+            });
+        }
+    }
+
+    /// Mark the frames from take_new_frames() as encoded and save the state
+    pub fn finish(&mut self) -> Result<(), EncodingError> {
+        if let Some(last) = self.pending_last_frame.take() {
+            self.state.last_frame = Some(last);
+        }
+        self.save()
+    }
+
+    /// Write the state to a temporary file and move it into place
+    fn save(&self) -> Result<(), EncodingError> {
+        let raw = toml::to_string(&self.state).map_err(|err| {
+            EncodingError::InvalidSettings(format!("failed to save state: {}", err))
+        })?;
+        let temp = self.state_path.with_extension("tmp");
+        fs::write(&temp, raw)?;
+        fs::rename(&temp, &self.state_path)?;
+        Ok(())
+    }
+}
+
+/// path, made absolute by resolving its folder, which must exist
+fn absolute_path(path: &Path) -> Result<PathBuf, EncodingError> {
+    let folder = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::canonicalize(parent)?,
+        _ => std::env::current_dir()?,
+    };
+    match path.file_name() {
+        Some(name) => Ok(folder.join(name)),
+        None => Err(EncodingError::InvalidSettings(format!(
+            "invalid state file {:?}",
+            path
+        ))),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IncrementalExport;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    pub fn test_tracks_new_frames() {
+        let folder = Path::new("test/output/incremental");
+        if folder.exists() {
+            fs::remove_dir_all(folder).unwrap();
+        }
+        let state_path = Path::new("test/output/incremental/state.toml");
+        let frames: Vec<PathBuf> = (1..=4)
+            .map(|i| PathBuf::from(format!("out/158306580{}000-x.png", i)))
+            .collect();
+
+        let mut export = IncrementalExport::open(state_path, folder, "a").unwrap();
+        assert_eq!(export.take_new_frames(&frames[0..3]).len(), 3);
+        let segment = export.next_segment_path();
+        fs::write(&segment, "").unwrap();
+        export.add_segment(&segment, &frames[0..2]);
+        export.finish().unwrap();
+
+        let mut export = IncrementalExport::open(state_path, folder, "a").unwrap();
+        assert_eq!(export.take_new_frames(&frames), vec![frames[3].clone()]);
+        assert_eq!(export.segments(), vec![segment.clone()]);
+        assert_eq!(export.state().segments[0].frames, 2);
+
+        // Changing the settings starts again
+        let mut export = IncrementalExport::open(state_path, folder, "b").unwrap();
+        assert_eq!(export.take_new_frames(&frames).len(), 4);
+        assert!(!segment.exists());
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
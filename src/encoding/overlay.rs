@@ -0,0 +1,230 @@
+use crate::encoding::bitmap_font::BitmapFont;
+use crate::encoding::error::EncodingError;
+use crate::encoding::pipeline::{ExportFrame, ExportStage};
+use crate::resources::TimeSnapshot;
+use chrono::{DateTime, Local, Utc};
+use image::{Rgb, RgbImage};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Which corner of the frame the overlay is drawn in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverlayPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl FromStr for OverlayPosition {
+    type Err = EncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "top-left" => Ok(OverlayPosition::TopLeft),
+            "top-right" => Ok(OverlayPosition::TopRight),
+            "bottom-left" => Ok(OverlayPosition::BottomLeft),
+            "bottom-right" => Ok(OverlayPosition::BottomRight),
+            _ => Err(EncodingError::InvalidSettings(format!(
+                "{} is not a valid overlay position; use top-left, top-right, bottom-left or bottom-right",
+                s
+            ))),
+        }
+    }
+}
+
+/// Parse a colour as either "#rrggbb" or "r,g,b"
+pub fn parse_color(s: &str) -> Result<Rgb<u8>, EncodingError> {
+    let invalid = || EncodingError::InvalidSettings(format!("{} is not a valid colour", s));
+    let s = s.trim();
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        return Ok(Rgb([channel(0)?, channel(2)?, channel(4)?]));
+    }
+    let parts = s
+        .split(',')
+        .map(|part| str::parse::<u8>(part.trim()).map_err(|_| invalid()))
+        .collect::<Result<Vec<u8>, EncodingError>>()?;
+    match parts.as_slice() {
+        [r, g, b] => Ok(Rgb([*r, *g, *b])),
+        _ => Err(invalid()),
+    }
+}
+
+/// Text burned into a corner of a frame. The text is a template which may contain
+/// {utc}, {local}, {date}, {time} and {camera}; a newline starts a new line.
+pub struct Overlay {
+    template: String,
+    camera: String,
+    position: OverlayPosition,
+    font: BitmapFont,
+    scale: u32,
+    color: Rgb<u8>,
+    background: Option<Rgb<u8>>,
+}
+
+impl Overlay {
+    pub fn new(template: &str) -> Overlay {
+        Overlay {
+            template: template.to_string(),
+            camera: String::new(),
+            position: OverlayPosition::BottomLeft,
+            font: BitmapFont::new(),
+            scale: 1,
+            color: Rgb([255, 255, 255]),
+            background: Some(Rgb([0, 0, 0])),
+        }
+    }
+
+    pub fn with_camera(mut self, camera: &str) -> Overlay {
+        self.camera = camera.to_string();
+        self
+    }
+
+    pub fn with_position(mut self, position: OverlayPosition) -> Overlay {
+        self.position = position;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Overlay {
+        self.scale = scale.max(1);
+        self.font = BitmapFont::with_scale(self.scale);
+        self
+    }
+
+    pub fn with_color(mut self, color: Rgb<u8>) -> Overlay {
+        self.color = color;
+        self
+    }
+
+    /// The colour of the box drawn behind the text; None to draw the text only
+    pub fn with_background(mut self, background: Option<Rgb<u8>>) -> Overlay {
+        self.background = background;
+        self
+    }
+
+    /// The template with every token replaced
+    pub fn text(&self, time: &DateTime<Utc>) -> String {
+        let local = time.with_timezone(&Local);
+        self.template
+            .replace("{utc}", &time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .replace("{local}", &local.format("%Y-%m-%d %H:%M:%S").to_string())
+            .replace("{date}", &local.format("%Y-%m-%d").to_string())
+            .replace("{time}", &local.format("%H:%M:%S").to_string())
+            .replace("{camera}", &self.camera)
+    }
+
+    pub fn render(&self, image: &mut RgbImage, time: &DateTime<Utc>) {
+        let text = self.text(time);
+        let lines: Vec<&str> = text.lines().collect();
+        let line_height = self.font.measure("0").1 + self.scale * 2;
+        let width = lines
+            .iter()
+            .map(|line| self.font.measure(line).0)
+            .max()
+            .unwrap_or(0);
+        let height = line_height * lines.len() as u32;
+        if width == 0 {
+            return;
+        }
+
+        let padding = self.scale * 2;
+        let (box_width, box_height) = (width + padding * 2, height + padding * 2);
+        let x = match self.position {
+            OverlayPosition::TopLeft | OverlayPosition::BottomLeft => 0,
+            _ => image.width() as i64 - box_width as i64,
+        };
+        let y = match self.position {
+            OverlayPosition::TopLeft | OverlayPosition::TopRight => 0,
+            _ => image.height() as i64 - box_height as i64,
+        };
+
+        if let Some(background) = self.background {
+            for py in y.max(0)..(y + box_height as i64).min(image.height() as i64) {
+                for px in x.max(0)..(x + box_width as i64).min(image.width() as i64) {
+                    image.put_pixel(px as u32, py as u32, background);
+                }
+            }
+        }
+        for (index, line) in lines.iter().enumerate() {
+            let line_y =
+                y + padding as i64 + (index as u32 * line_height) as i64 + self.scale as i64;
+            self.font
+                .draw(image, line, x + padding as i64, line_y, self.color);
+        }
+    }
+}
+
+/// Draws the overlay on exported frames, using the capture time from each frame's filename.
+/// Frames without a timestamp in their filename are passed through unchanged.
+pub struct OverlayStage {
+    overlay: Overlay,
+    times: Vec<Option<DateTime<Utc>>>,
+}
+
+impl OverlayStage {
+    pub fn new(overlay: Overlay) -> OverlayStage {
+        OverlayStage {
+            overlay,
+            times: Vec::new(),
+        }
+    }
+}
+
+impl ExportStage for OverlayStage {
+    fn prepare(&mut self, frames: &[PathBuf]) -> Result<(), EncodingError> {
+        self.times = frames
+            .iter()
+            .map(|path| TimeSnapshot::from_frame_path(path).map(|t| t.utc))
+            .collect();
+        Ok(())
+    }
+
+    fn process(&mut self, mut frame: ExportFrame) -> Result<Vec<ExportFrame>, EncodingError> {
+        if let Some(Some(time)) = self.times.get(frame.index) {
+            self.overlay.render(&mut frame.image, time);
+        }
+        Ok(vec![frame])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_color, Overlay, OverlayPosition};
+    use crate::resources::TimeSnapshot;
+    use image::{Rgb, RgbImage};
+    use std::str::FromStr;
+
+    #[test]
+    pub fn test_parse_settings() {
+        assert_eq!(parse_color("#ff8000").unwrap(), Rgb([255, 128, 0]));
+        assert_eq!(parse_color("1, 2, 3").unwrap(), Rgb([1, 2, 3]));
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("1,2").is_err());
+        assert!(parse_color("#a€bc").is_err());
+        assert_eq!(
+            OverlayPosition::from_str("Top-Right").unwrap(),
+            OverlayPosition::TopRight
+        );
+        assert!(OverlayPosition::from_str("middle").is_err());
+    }
+
+    #[test]
+    pub fn test_render_in_corner() {
+        let time = TimeSnapshot::from_timestamp(1_583_065_800_000).utc;
+        let overlay = Overlay::new("{camera} {utc}")
+            .with_camera("garden")
+            .with_position(OverlayPosition::TopRight)
+            .with_background(Some(Rgb([255, 0, 0])));
+        assert_eq!(overlay.text(&time), "garden 2020-03-01 12:30:00 UTC");
+
+        let mut image = RgbImage::new(320, 80);
+        overlay.render(&mut image, &time);
+        assert_eq!(*image.get_pixel(319, 0), Rgb([255, 0, 0]));
+        assert_eq!(*image.get_pixel(0, 79), Rgb([0, 0, 0]));
+        assert!(image.pixels().any(|p| *p == Rgb([255, 255, 255])));
+    }
+}
@@ -0,0 +1,153 @@
+use crate::encoding::error::EncodingError;
+use crate::encoding::StagingFolder;
+use image::RgbImage;
+use std::path::{Path, PathBuf};
+
+/// A frame on its way through the export stages
+pub struct ExportFrame {
+    pub image: RgbImage,
+
+    /// The position of the source frame in the exported sequence
+    pub index: usize,
+}
+
+/// A step applied to every frame during export. A stage may drop frames, or emit several
+/// frames for one input (eg. interpolation), and may hold frames back until flush().
+pub trait ExportStage {
+    /// Called once with the full sequence before any frames are processed.
+    fn prepare(&mut self, _frames: &[PathBuf]) -> Result<(), EncodingError> {
+        Ok(())
+    }
+
+    fn process(&mut self, frame: ExportFrame) -> Result<Vec<ExportFrame>, EncodingError>;
+
+    /// Emit any frames still held back at the end of the sequence.
+    fn flush(&mut self) -> Result<Vec<ExportFrame>, EncodingError> {
+        Ok(Vec::new())
+    }
+}
+
+/// Runs frames through each stage in order and writes the results to a staging folder.
+#[derive(Default)]
+pub struct ExportPipeline {
+    stages: Vec<Box<dyn ExportStage>>,
+}
+
+impl ExportPipeline {
+    pub fn new() -> ExportPipeline {
+        Default::default()
+    }
+
+    pub fn add<T: ExportStage + 'static>(&mut self, stage: T) {
+        self.stages.push(Box::new(stage));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn run(
+        &mut self,
+        frames: &[PathBuf],
+        staging: &mut StagingFolder,
+    ) -> Result<(), EncodingError> {
+        if self.stages.is_empty() {
+            for frame in frames.iter() {
+                staging.add(frame)?;
+            }
+            return Ok(());
+        }
+
+        for stage in self.stages.iter_mut() {
+            stage.prepare(frames)?;
+        }
+        for (index, path) in frames.iter().enumerate() {
+            let frame = ExportFrame {
+                image: load(path)?,
+                index,
+            };
+            let output = self.process_from(0, vec![frame])?;
+            save_all(output, staging)?;
+        }
+        for offset in 0..self.stages.len() {
+            let flushed = self.stages[offset].flush()?;
+            let output = self.process_from(offset + 1, flushed)?;
+            save_all(output, staging)?;
+        }
+        Ok(())
+    }
+
+    /// Pass the frames through the stages starting at `first`
+    fn process_from(
+        &mut self,
+        first: usize,
+        mut frames: Vec<ExportFrame>,
+    ) -> Result<Vec<ExportFrame>, EncodingError> {
+        for stage in self.stages.iter_mut().skip(first) {
+            let mut output = Vec::new();
+            for frame in frames.into_iter() {
+                output.extend(stage.process(frame)?);
+            }
+            frames = output;
+        }
+        Ok(frames)
+    }
+}
+
+fn load(path: &Path) -> Result<RgbImage, EncodingError> {
+    Ok(image::open(path)?.to_rgb8())
+}
+
+fn save_all(frames: Vec<ExportFrame>, staging: &mut StagingFolder) -> Result<(), EncodingError> {
+    for frame in frames.into_iter() {
+        frame.image.save(staging.next_path())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExportFrame, ExportPipeline, ExportStage};
+    use crate::encoding::error::EncodingError;
+    use crate::encoding::StagingFolder;
+    use std::path::{Path, PathBuf};
+
+    /// Emits every frame twice, except the last, which is held until flush
+    struct Doubler {
+        held: Option<ExportFrame>,
+    }
+
+    impl ExportStage for Doubler {
+        fn process(&mut self, frame: ExportFrame) -> Result<Vec<ExportFrame>, EncodingError> {
+            let mut output = Vec::new();
+            if let Some(previous) = self.held.replace(frame) {
+                output.push(ExportFrame {
+                    image: previous.image.clone(),
+                    index: previous.index,
+                });
+                output.push(previous);
+            }
+            Ok(output)
+        }
+
+        fn flush(&mut self) -> Result<Vec<ExportFrame>, EncodingError> {
+            Ok(self.held.take().into_iter().collect())
+        }
+    }
+
+    #[test]
+    pub fn test_run_stages() {
+        let frames: Vec<PathBuf> = (0..3)
+            .map(|i| PathBuf::from(format!("test/data/frames/frame_0000000{}.png", i)))
+            .collect();
+        let mut staging = StagingFolder::new(Path::new("test/output/pipeline")).unwrap();
+        let mut pipeline = ExportPipeline::new();
+        pipeline.add(Doubler { held: None });
+        pipeline.add(Doubler { held: None });
+        pipeline.run(&frames, &mut staging).unwrap();
+
+        // 3 frames, doubled except the last, twice: 5 then 9
+        assert_eq!(staging.len(), 9);
+        staging.remove().unwrap();
+    }
+}
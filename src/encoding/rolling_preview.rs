@@ -0,0 +1,121 @@
+use crate::encoding::error::EncodingError;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::imageops::FilterType;
+use image::{Delay, DynamicImage, ImageBuffer, Rgb, RgbImage};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A small looping GIF of the most recent frames, rewritten after every capture so progress
+/// can be checked without stopping the capture to run assemble.
+pub struct RollingPreview {
+    path: PathBuf,
+    max_frames: usize,
+    width: u32,
+    delay: u32,
+    frames: VecDeque<RgbImage>,
+}
+
+impl RollingPreview {
+    pub fn new(path: &Path, max_frames: usize, width: u32) -> RollingPreview {
+        RollingPreview {
+            path: path.to_path_buf(),
+            max_frames: max_frames.max(1),
+            width: width.max(16),
+            delay: 100,
+            frames: VecDeque::new(),
+        }
+    }
+
+    /// How long each frame is shown for, in ms
+    pub fn with_delay(mut self, delay: u32) -> RollingPreview {
+        self.delay = delay.max(10);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Start from frames which were saved before the capture started; only the last
+    /// max_frames are used
+    pub fn seed(&mut self, frames: &[PathBuf]) -> Result<(), EncodingError> {
+        let start = frames.len().saturating_sub(self.max_frames);
+        for path in frames[start..].iter() {
+            let image = image::open(path)?.to_rgb8();
+            self.push(&image);
+        }
+        Ok(())
+    }
+
+    /// Add a frame, dropping the oldest frame if the preview is full
+    pub fn push<C>(&mut self, frame: &ImageBuffer<Rgb<u8>, C>)
+    where
+        C: Deref<Target = [u8]>,
+    {
+        let height = (self.width * frame.height() / frame.width().max(1)).max(1);
+        self.frames.push_back(image::imageops::resize(
+            frame,
+            self.width,
+            height,
+            FilterType::Triangle,
+        ));
+        while self.frames.len() > self.max_frames {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Write the preview to a temporary file and move it into place, so a viewer never sees
+    /// a partly written file
+    pub fn write(&self) -> Result<(), EncodingError> {
+        if self.frames.is_empty() {
+            return Ok(());
+        }
+        let temp = self.path.with_extension("tmp");
+        {
+            let mut encoder = GifEncoder::new(BufWriter::new(File::create(&temp)?));
+            encoder.set_repeat(Repeat::Infinite)?;
+            for frame in self.frames.iter() {
+                let buffer = DynamicImage::ImageRgb8(frame.clone()).to_rgba8();
+                encoder.encode_frame(image::Frame::from_parts(
+                    buffer,
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(self.delay, 1),
+                ))?;
+            }
+        }
+        fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RollingPreview;
+    use image::{GenericImageView, Rgb, RgbImage};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    pub fn test_rolling_preview() {
+        fs::create_dir_all("test/output").unwrap();
+        let path = Path::new("test/output/preview.gif");
+        let mut preview = RollingPreview::new(path, 3, 32).with_delay(50);
+        for i in 0..5u8 {
+            preview.push(&RgbImage::from_pixel(64, 48, Rgb([i * 40, 0, 0])));
+        }
+        assert_eq!(preview.len(), 3);
+        preview.write().unwrap();
+
+        let decoded = image::open(path).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (32, 24));
+        fs::remove_file(path).unwrap();
+    }
+}
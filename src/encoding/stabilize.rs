@@ -0,0 +1,276 @@
+use crate::analysis::{Aligner, Alignment};
+use crate::encoding::error::EncodingError;
+use crate::encoding::pipeline::{ExportFrame, ExportStage};
+use image::{Rgb, RgbImage};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Which frame each frame is aligned against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlignReference {
+    /// Align every frame against the first frame; best for slow drift
+    First,
+
+    /// Align each frame against the one before it, and accumulate the offsets; copes with
+    /// scenes that change a lot over the sequence, but errors add up
+    Previous,
+}
+
+impl FromStr for AlignReference {
+    type Err = EncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "first" => Ok(AlignReference::First),
+            "previous" => Ok(AlignReference::Previous),
+            _ => Err(EncodingError::InvalidSettings(format!(
+                "{} is not a valid stabilize reference; use first or previous",
+                s
+            ))),
+        }
+    }
+}
+
+/// A region of the frame, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Removes camera drift and shake by aligning every frame with a reference frame, then
+/// crops all frames to the region which is covered in every aligned frame.
+pub struct Stabilizer {
+    reference: AlignReference,
+    max_rotation: f32,
+    max_shift: f32,
+    crop: bool,
+    alignments: Vec<Alignment>,
+    crop_rect: Option<CropRect>,
+}
+
+impl Stabilizer {
+    pub fn new(reference: AlignReference) -> Stabilizer {
+        Stabilizer {
+            reference,
+            max_rotation: 0.0,
+            max_shift: 0.1,
+            crop: true,
+            alignments: Vec::new(),
+            crop_rect: None,
+        }
+    }
+
+    /// Also correct rotations of up to this many degrees
+    pub fn with_rotation(mut self, max_degrees: f32) -> Stabilizer {
+        self.max_rotation = max_degrees.abs();
+        self
+    }
+
+    /// Offsets larger than this fraction of the frame are treated as failed matches and the
+    /// frame is left where it is
+    pub fn with_max_shift(mut self, max_shift: f32) -> Stabilizer {
+        self.max_shift = max_shift.abs();
+        self
+    }
+
+    /// Crop to the stable region; if false, frames keep their size and the uncovered edges
+    /// are filled with black
+    pub fn with_crop(mut self, crop: bool) -> Stabilizer {
+        self.crop = crop;
+        self
+    }
+
+    /// The offset of each frame from the reference, as found by prepare()
+    pub fn alignments(&self) -> &[Alignment] {
+        &self.alignments
+    }
+
+    /// Estimate the offset of each frame from the reference
+    pub fn estimate(&mut self, frames: &[RgbImage]) -> Result<(), EncodingError> {
+        self.estimate_from(frames.iter().cloned().map(Ok))
+    }
+
+    fn estimate_from<I>(&mut self, frames: I) -> Result<(), EncodingError>
+    where
+        I: Iterator<Item = Result<RgbImage, EncodingError>>,
+    {
+        let mut aligner = Aligner::new(256).with_rotation(self.max_rotation);
+        let mut previous = Alignment::default();
+        let mut size = None;
+        self.alignments.clear();
+        for frame in frames {
+            let frame = frame?;
+            if size.is_none() {
+                aligner.set_reference(&frame);
+                size = Some(frame.dimensions());
+                self.alignments.push(Alignment::default());
+                continue;
+            }
+            let found = self.checked(aligner.align(&frame)?, frame.width(), frame.height());
+            let alignment = match self.reference {
+                AlignReference::First => found,
+                AlignReference::Previous => {
+                    aligner.set_reference(&frame);
+                    previous = previous.then(&found);
+                    previous
+                }
+            };
+            self.alignments.push(alignment);
+        }
+        self.crop_rect = size.map(|(width, height)| self.stable_region(width, height));
+        Ok(())
+    }
+
+    /// Discard matches which move the frame further than max_shift
+    fn checked(&self, alignment: Alignment, width: u32, height: u32) -> Alignment {
+        if alignment.dx.abs() > width as f32 * self.max_shift
+            || alignment.dy.abs() > height as f32 * self.max_shift
+        {
+            Alignment::default()
+        } else {
+            alignment
+        }
+    }
+
+    /// The largest region covered by every aligned frame, with an even width and height
+    pub fn stable_region(&self, width: u32, height: u32) -> CropRect {
+        let (w, h) = (width as f32, height as f32);
+        let (mut left, mut top, mut right, mut bottom) = (0f32, 0f32, w, h);
+        for alignment in self.alignments.iter() {
+            let (sin, cos) = alignment.angle.to_radians().sin_cos();
+            let margin_x = h / 2.0 * sin.abs() + w / 2.0 * (1.0 - cos);
+            let margin_y = w / 2.0 * sin.abs() + h / 2.0 * (1.0 - cos);
+            left = left.max(-alignment.dx + margin_x);
+            right = right.min(w - alignment.dx - margin_x);
+            top = top.max(-alignment.dy + margin_y);
+            bottom = bottom.min(h - alignment.dy - margin_y);
+        }
+        let x = (left.ceil().max(0.0) as u32).min(width - 1);
+        let y = (top.ceil().max(0.0) as u32).min(height - 1);
+        let crop_width = ((right.floor() as i64 - x as i64).max(2) as u32).min(width - x) & !1;
+        let crop_height = ((bottom.floor() as i64 - y as i64).max(2) as u32).min(height - y) & !1;
+        CropRect {
+            x,
+            y,
+            width: crop_width.max(2),
+            height: crop_height.max(2),
+        }
+    }
+}
+
+impl ExportStage for Stabilizer {
+    fn prepare(&mut self, frames: &[PathBuf]) -> Result<(), EncodingError> {
+        self.estimate_from(frames.iter().map(|path| Ok(image::open(path)?.to_rgb8())))
+    }
+
+    fn process(&mut self, frame: ExportFrame) -> Result<Vec<ExportFrame>, EncodingError> {
+        let alignment = match self.alignments.get(frame.index) {
+            Some(a) => *a,
+            None => return Ok(vec![frame]),
+        };
+        let warped = warp(&frame.image, &alignment);
+        let image = match (self.crop, self.crop_rect) {
+            (true, Some(rect)) => {
+                image::imageops::crop_imm(&warped, rect.x, rect.y, rect.width, rect.height)
+                    .to_image()
+            }
+            _ => warped,
+        };
+        Ok(vec![ExportFrame {
+            image,
+            index: frame.index,
+        }])
+    }
+}
+
+/// Move the frame back onto the reference, sampling bilinearly
+fn warp(image: &RgbImage, alignment: &Alignment) -> RgbImage {
+    let (width, height) = image.dimensions();
+    if alignment.dx == 0.0 && alignment.dy == 0.0 && alignment.angle == 0.0 {
+        return image.clone();
+    }
+    RgbImage::from_fn(width, height, |x, y| {
+        let (sx, sy) = alignment.map(x as f32, y as f32, width, height);
+        if sx < 0.0 || sy < 0.0 || sx > (width - 1) as f32 || sy > (height - 1) as f32 {
+            return Rgb([0, 0, 0]);
+        }
+        let (x0, y0) = (sx.floor() as u32, sy.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (fx, fy) = (sx - x0 as f32, sy - y0 as f32);
+        let mut pixel = [0u8; 3];
+        for (c, value) in pixel.iter_mut().enumerate() {
+            let top = image.get_pixel(x0, y0)[c] as f32 * (1.0 - fx)
+                + image.get_pixel(x1, y0)[c] as f32 * fx;
+            let bottom = image.get_pixel(x0, y1)[c] as f32 * (1.0 - fx)
+                + image.get_pixel(x1, y1)[c] as f32 * fx;
+            *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+        Rgb(pixel)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlignReference, CropRect, Stabilizer};
+    use crate::analysis::Alignment;
+    use crate::encoding::pipeline::{ExportFrame, ExportStage};
+    use image::{Rgb, RgbImage};
+
+    fn scene(dx: u32, dy: u32) -> RgbImage {
+        RgbImage::from_fn(200, 120, |x, y| {
+            let (x, y) = (x as f32 - dx as f32, y as f32 - dy as f32);
+            let v = ((x * 0.13).sin() * (y * 0.09).cos() * 80.0 + 120.0) as u8;
+            let v = if (x - 60.0).powi(2) + (y - 50.0).powi(2) < 300.0 {
+                250
+            } else {
+                v
+            };
+            Rgb([v, v, v])
+        })
+    }
+
+    #[test]
+    pub fn test_stabilize_and_crop() {
+        let frames = vec![scene(0, 0), scene(6, 0), scene(6, 4)];
+        let mut stabilizer = Stabilizer::new(AlignReference::Previous);
+        stabilizer.estimate(&frames).unwrap();
+        let last = stabilizer.alignments()[2];
+        assert!((last.dx - 6.0).abs() < 1.5, "{:?}", last);
+        assert!((last.dy - 4.0).abs() < 1.5, "{:?}", last);
+
+        let output = stabilizer
+            .process(ExportFrame {
+                image: frames[2].clone(),
+                index: 2,
+            })
+            .unwrap();
+        let (width, height) = output[0].image.dimensions();
+        assert!((190..=194).contains(&width) && width % 2 == 0);
+        assert!((112..=116).contains(&height) && height % 2 == 0);
+    }
+
+    #[test]
+    pub fn test_stable_region() {
+        let mut stabilizer = Stabilizer::new(AlignReference::First);
+        stabilizer.alignments = vec![
+            Alignment::default(),
+            Alignment {
+                dx: 10.0,
+                dy: -4.0,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            stabilizer.stable_region(100, 50),
+            CropRect {
+                x: 0,
+                y: 4,
+                width: 90,
+                height: 46
+            }
+        );
+    }
+}
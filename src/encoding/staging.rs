@@ -0,0 +1,100 @@
+use crate::encoding::error::EncodingError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const MARKER: &str = ".snapshot-staging";
+
+/// A scratch folder of sequentially numbered frames to hand to ffmpeg, so that a subset
+/// of the captured frames can be exported without touching the output folder.
+///
+/// The folder is marked when it is created, and only a marked (or empty) folder is ever
+/// cleared, so a staging_folder setting pointing at real data can't delete it.
+pub struct StagingFolder {
+    path: PathBuf,
+    count: usize,
+}
+
+impl StagingFolder {
+    /// Create the folder, removing anything left over from a previous export. An existing
+    /// folder which isn't empty and wasn't created by `StagingFolder` is an error.
+    pub fn new(path: &Path) -> Result<StagingFolder, EncodingError> {
+        if path.exists() {
+            if path.join(MARKER).exists() {
+                fs::remove_dir_all(path)?;
+            } else if fs::read_dir(path)?.next().is_some() {
+                return Err(EncodingError::InvalidSettings(format!(
+                    "staging folder {:?} already exists and is not a staging folder; \
+                     refusing to delete it",
+                    path
+                )));
+            }
+        }
+        fs::create_dir_all(path)?;
+        fs::write(path.join(MARKER), b"")?;
+        Ok(StagingFolder {
+            path: path.to_path_buf(),
+            count: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The path the next frame should be written to
+    pub fn next_path(&mut self) -> PathBuf {
+        let path = self.path.join(format!("frame_{:08}.png", self.count));
+        self.count += 1;
+        path
+    }
+
+    /// Add an existing frame; hard linked where possible to avoid a copy
+    pub fn add(&mut self, frame: &Path) -> Result<(), EncodingError> {
+        let target = self.next_path();
+        if fs::hard_link(frame, &target).is_err() {
+            fs::copy(frame, &target)?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(self) -> Result<(), EncodingError> {
+        if self.path.join(MARKER).exists() {
+            fs::remove_dir_all(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StagingFolder;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    pub fn test_refuses_unmarked_folder() {
+        let path = Path::new("test/output/staging_guard");
+        let _ = fs::remove_dir_all(path);
+        fs::create_dir_all(path).unwrap();
+        fs::write(path.join("1000-frame.png"), b"frame").unwrap();
+        assert!(StagingFolder::new(path).is_err());
+        assert!(path.join("1000-frame.png").exists());
+        fs::remove_dir_all(path).unwrap();
+
+        let mut staging = StagingFolder::new(path).unwrap();
+        fs::write(staging.next_path(), b"frame").unwrap();
+        let staging = StagingFolder::new(path).unwrap();
+        assert!(staging.is_empty());
+        assert!(!path.join("frame_00000000.png").exists());
+        staging.remove().unwrap();
+        assert!(!path.exists());
+    }
+}
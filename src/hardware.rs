@@ -1,9 +1,22 @@
+mod camera_registry;
+mod fault_camera;
 mod ffmpeg_camera;
+mod http_camera;
+mod http_client;
 mod mock_camera;
+mod pattern_camera;
+mod pi_camera;
+mod rtsp_camera;
+#[cfg(test)]
+mod test_server;
 
+pub use self::camera_registry::{
+    CameraBackend, CameraConstructor, CameraRegistry, SettingKind, SettingSchema,
+};
 pub use self::error::HardwareError;
-use crate::hardware::ffmpeg_camera::AvCamera;
-use crate::hardware::mock_camera::MockCamera;
+pub use self::fault_camera::{Fault, FaultPlan, FaultyCamera};
+pub use self::pattern_camera::Pattern;
+pub use self::pi_camera::PiCameraSettings;
 use crate::resources::ConfigMap;
 use image::{ImageBuffer, Rgb};
 
@@ -20,28 +33,94 @@ pub trait CameraLike {
     fn next(&mut self) -> Result<Frame, HardwareError>;
 }
 
+/// Creates cameras from the [settings] block; the `backend` key selects a backend from
+/// the registry, defaulting to the ffmpeg backend. For compatibility `use_mock` selects
+/// the mock camera.
+/// If any `fault_` settings are present the camera is wrapped in a FaultyCamera.
 pub struct CameraFactory {
     config: ConfigMap,
+    registry: CameraRegistry,
 }
 
 impl CameraFactory {
     pub fn new(config: ConfigMap) -> CameraFactory {
-        CameraFactory { config }
+        CameraFactory::with_registry(config, Default::default())
+    }
+
+    pub fn with_registry(config: ConfigMap, registry: CameraRegistry) -> CameraFactory {
+        CameraFactory { config, registry }
+    }
+
+    pub fn registry(&self) -> &CameraRegistry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut CameraRegistry {
+        &mut self.registry
     }
 
     pub fn create_camera(&self) -> Result<Box<dyn CameraLike + 'static>, HardwareError> {
-        let mut camera = if self.use_mock() {
-            Box::new(MockCamera::new()) as Box<dyn CameraLike + 'static>
-        } else {
-            Box::new(AvCamera::new()) as Box<dyn CameraLike + 'static>
-        };
+        let mut camera = self.registry.create(&self.backend_name(), &self.config)?;
+        if let Some(plan) = FaultPlan::from_config(&self.config)? {
+            camera = Box::new(FaultyCamera::new(camera, plan));
+        }
         camera.initialize(self.config.clone())?;
         Ok(camera)
     }
 
-    fn use_mock(&self) -> bool {
-        self.config.flag("use_mock")
+    fn backend_name(&self) -> String {
+        if self.config.flag("use_mock") {
+            return "mock".to_string();
+        }
+        self.config
+            .get_string("backend")
+            .unwrap_or_else(|| "ffmpeg".to_string())
+    }
+}
+
+/// xorshift; good enough for noise and faults, and reproducible for a given seed
+pub(crate) fn xorshift(seed: &mut u64) -> u64 {
+    let mut x = *seed;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    *seed = x;
+    x
+}
+
+/// Parse a resolution in the form AAAxBBB, eg. 640x480
+pub(crate) fn as_resolution_tuple(value: &str) -> Result<(u32, u32), HardwareError> {
+    let parts: Vec<String> = value.split("x").map(|v| v.to_string()).collect();
+    if parts.len() != 2 {
+        return Err(HardwareError::InvalidSettings(format!(
+            "{} is not a valid resolution; use the format AAAxBBB, eg. 640x480",
+            value
+        )));
     }
+
+    let p1 = str::parse::<u32>(&parts[0]);
+    let p1_u32 = match p1 {
+        Ok(v) => v,
+        Err(_) => {
+            return Err(HardwareError::InvalidSettings(format!(
+                "{} in {} is not a valid resolution; use the format AAAxBBB, eg. 640x480",
+                &parts[0], value
+            )));
+        }
+    };
+
+    let p2 = str::parse::<u32>(&parts[1]);
+    let p2_u32 = match p2 {
+        Ok(v) => v,
+        Err(_) => {
+            return Err(HardwareError::InvalidSettings(format!(
+                "{} in {} is not a valid resolution; use the format AAAxBBB, eg. 640x480",
+                &parts[1], value
+            )));
+        }
+    };
+
+    Ok((p1_u32, p2_u32))
 }
 
 mod error {
@@ -93,14 +172,31 @@ mod error {
 
     impl From<rust_ffmpeg_capture::CaptureError> for HardwareError {
         fn from(err: rust_ffmpeg_capture::CaptureError) -> Self {
-            HardwareError::DeviceFailed(format!("{}", err))
+            match err {
+                rust_ffmpeg_capture::CaptureError::EndOfStream => {
+                    HardwareError::DeviceNoLongerAvailable("End of stream".to_string())
+                }
+                err => HardwareError::DeviceFailed(format!("{}", err)),
+            }
+        }
+    }
+
+    #[cfg(feature = "pi")]
+    impl From<rust_pi_capture::CaptureError> for HardwareError {
+        fn from(err: rust_pi_capture::CaptureError) -> Self {
+            match err {
+                rust_pi_capture::CaptureError::InvalidSetting(msg) => {
+                    HardwareError::InvalidSettings(msg)
+                }
+                err => HardwareError::DeviceFailed(format!("{}", err)),
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::CameraFactory;
+    use super::{CameraFactory, HardwareError};
     use crate::resources::ConfigMap;
 
     #[test]
@@ -117,4 +213,51 @@ mod tests {
 
         camera.shutdown().unwrap();
     }
+
+    #[test]
+    pub fn test_backend_factory() {
+        let mut config = ConfigMap::new();
+        config.set("backend", "mock");
+        config.set("use_mock_folder", "test/data/frames");
+
+        let mut camera = CameraFactory::new(config).create_camera().unwrap();
+        assert!(camera.next().is_ok());
+        camera.shutdown().unwrap();
+    }
+
+    #[test]
+    pub fn test_factory_injects_faults() {
+        let mut config = ConfigMap::new();
+        config.set("backend", "pattern");
+        config.set("fault_plan", "error:io");
+
+        let mut camera = CameraFactory::new(config).create_camera().unwrap();
+        assert!(camera.next().is_err());
+        assert!(camera.next().is_ok());
+    }
+
+    #[test]
+    pub fn test_unknown_backend_fails() {
+        let mut config = ConfigMap::new();
+        config.set("backend", "v4l2");
+
+        match CameraFactory::new(config).create_camera() {
+            Err(HardwareError::InvalidSettings(message)) => {
+                assert!(message.contains("v4l2"));
+                assert!(message.contains("ffmpeg, http, mmal, mock"));
+            }
+            Err(err) => panic!("expected InvalidSettings, got {:?}", err),
+            Ok(_) => panic!("expected an unknown backend to fail"),
+        }
+    }
+
+    #[cfg(not(feature = "pi"))]
+    #[test]
+    pub fn test_mmal_factory_requires_feature() {
+        let mut config = ConfigMap::new();
+        config.set("backend", "mmal");
+
+        let camera = CameraFactory::new(config).create_camera();
+        assert!(camera.is_err());
+    }
 }
@@ -0,0 +1,381 @@
+use crate::hardware::error::HardwareError;
+use crate::hardware::ffmpeg_camera::AvCamera;
+use crate::hardware::http_camera::HttpCamera;
+use crate::hardware::mock_camera::MockCamera;
+use crate::hardware::pattern_camera::PatternCamera;
+#[cfg(feature = "pi")]
+use crate::hardware::pi_camera::PiCamera;
+use crate::hardware::rtsp_camera::RtspCamera;
+use crate::hardware::{as_resolution_tuple, CameraLike};
+use crate::resources::ConfigMap;
+use std::collections::HashMap;
+
+pub type CameraConstructor = Box<dyn Fn() -> Result<Box<dyn CameraLike + 'static>, HardwareError>>;
+
+/// The type of value a setting holds, used to check the manifest before a camera is created.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingKind {
+    Text,
+    Number,
+    Flag,
+    Resolution,
+}
+
+/// Describes a single key in the [settings] block that a backend understands.
+#[derive(Debug, Clone)]
+pub struct SettingSchema {
+    pub key: String,
+    pub kind: SettingKind,
+    pub required: bool,
+    pub description: String,
+}
+
+impl SettingSchema {
+    pub fn new(key: &str, kind: SettingKind, description: &str) -> SettingSchema {
+        SettingSchema {
+            key: key.to_string(),
+            kind,
+            required: false,
+            description: description.to_string(),
+        }
+    }
+
+    pub fn required(mut self) -> SettingSchema {
+        self.required = true;
+        self
+    }
+
+    pub fn validate(&self, config: &ConfigMap) -> Result<(), HardwareError> {
+        let value = match config.get_string(&self.key) {
+            Some(v) => v,
+            None if self.required => {
+                return Err(HardwareError::InvalidSettings(format!(
+                    "missing required setting {}: {}",
+                    self.key, self.description
+                )));
+            }
+            None => return Ok(()),
+        };
+        let valid = match self.kind {
+            SettingKind::Text => true,
+            SettingKind::Number => str::parse::<f64>(&value).is_ok(),
+            SettingKind::Flag => {
+                let value_lower = value.to_lowercase();
+                ["1", "0", "yes", "no", "true", "false"].contains(&value_lower.as_str())
+            }
+            SettingKind::Resolution => as_resolution_tuple(&value).is_ok(),
+        };
+        if !valid {
+            return Err(HardwareError::InvalidSettings(format!(
+                "{} is not a valid {:?} for setting {}",
+                value, self.kind, self.key
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub struct CameraBackend {
+    pub name: String,
+    pub schema: Vec<SettingSchema>,
+    constructor: CameraConstructor,
+}
+
+/// The set of named camera backends the `backend` setting can select from.
+/// Downstream crates can register their own CameraLike implementations here.
+pub struct CameraRegistry {
+    backends: HashMap<String, CameraBackend>,
+}
+
+impl CameraRegistry {
+    /// An empty registry; use Default::default() for one with the built in backends.
+    pub fn new() -> CameraRegistry {
+        CameraRegistry {
+            backends: HashMap::new(),
+        }
+    }
+
+    pub fn register<F>(&mut self, name: &str, schema: Vec<SettingSchema>, constructor: F)
+    where
+        F: Fn() -> Result<Box<dyn CameraLike + 'static>, HardwareError> + 'static,
+    {
+        let _ = self.backends.insert(
+            name.to_string(),
+            CameraBackend {
+                name: name.to_string(),
+                schema,
+                constructor: Box::new(constructor),
+            },
+        );
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.backends.contains_key(name)
+    }
+
+    pub fn backend(&self, name: &str) -> Option<&CameraBackend> {
+        self.backends.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.backends.keys().map(|k| k.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Check the config against the backend schema and construct an uninitialized camera.
+    pub fn create(
+        &self,
+        name: &str,
+        config: &ConfigMap,
+    ) -> Result<Box<dyn CameraLike + 'static>, HardwareError> {
+        let backend = match self.backends.get(name) {
+            Some(b) => b,
+            None => {
+                return Err(HardwareError::InvalidSettings(format!(
+                    "no camera backend named {}; available backends are: {}",
+                    name,
+                    self.names().join(", ")
+                )));
+            }
+        };
+        for setting in backend.schema.iter() {
+            setting.validate(config)?;
+        }
+        (backend.constructor)()
+    }
+}
+
+impl Default for CameraRegistry {
+    fn default() -> Self {
+        let mut registry = CameraRegistry::new();
+        registry.register("mock", mock_schema(), || Ok(Box::new(MockCamera::new())));
+        registry.register("pattern", pattern_schema(), || {
+            Ok(Box::new(PatternCamera::new()))
+        });
+        registry.register("ffmpeg", ffmpeg_schema(), || Ok(Box::new(AvCamera::new())));
+        registry.register("pi", pi_schema(), create_pi_camera);
+        registry.register("mmal", pi_schema(), create_pi_camera);
+        registry.register("http", http_schema(), || Ok(Box::new(HttpCamera::new())));
+        registry.register("rtsp", rtsp_schema(), || Ok(Box::new(RtspCamera::new())));
+        registry
+    }
+}
+
+fn mock_schema() -> Vec<SettingSchema> {
+    vec![
+        SettingSchema::new(
+            "use_mock_folder",
+            SettingKind::Text,
+            "folder of frames to play back",
+        ),
+        SettingSchema::new("use_mock_glob", SettingKind::Text, "eg. *.png,*.jpg"),
+        SettingSchema::new(
+            "use_mock_video",
+            SettingKind::Text,
+            "video file to play back",
+        ),
+        SettingSchema::new(
+            "use_mock_resolution",
+            SettingKind::Resolution,
+            "frame size of the video; frames are not scaled",
+        ),
+        SettingSchema::new(
+            "use_mock_repeat_frames",
+            SettingKind::Flag,
+            "loop back to the first frame at the end",
+        ),
+        SettingSchema::new(
+            "use_mock_realtime",
+            SettingKind::Flag,
+            "wait between frames as long as the original capture did",
+        ),
+        SettingSchema::new(
+            "use_mock_speed",
+            SettingKind::Number,
+            "realtime playback speed",
+        ),
+        SettingSchema::new(
+            "use_mock_fail_after",
+            SettingKind::Number,
+            "fail as if the device was lost after this many frames",
+        ),
+        SettingSchema::new(
+            "use_mock_delay",
+            SettingKind::Number,
+            "extra ms for every read",
+        ),
+    ]
+}
+
+fn pattern_schema() -> Vec<SettingSchema> {
+    vec![
+        SettingSchema::new("resolution", SettingKind::Resolution, "eg. 1920x1080"),
+        SettingSchema::new("pattern", SettingKind::Text, "bars, gradient or shapes"),
+        SettingSchema::new(
+            "pattern_noise",
+            SettingKind::Number,
+            "noise amplitude, 0-255",
+        ),
+        SettingSchema::new(
+            "pattern_timestamp",
+            SettingKind::Flag,
+            "burn in the time and frame number",
+        ),
+        SettingSchema::new("pattern_seed", SettingKind::Number, "noise seed"),
+    ]
+}
+
+fn ffmpeg_schema() -> Vec<SettingSchema> {
+    vec![
+        SettingSchema::new(
+            "input_format",
+            SettingKind::Text,
+            "libav input format, eg. v4l2; guessed from the device if empty",
+        ),
+        SettingSchema::new("device", SettingKind::Text, "libav device name"),
+        SettingSchema::new("resolution", SettingKind::Resolution, "eg. 640x480"),
+        SettingSchema::new("framerate", SettingKind::Number, "device framerate"),
+        SettingSchema::new("pixel_format", SettingKind::Text, "eg. yuv420p"),
+    ]
+}
+
+fn pi_schema() -> Vec<SettingSchema> {
+    vec![
+        SettingSchema::new("resolution", SettingKind::Resolution, "eg. 1920x1080"),
+        SettingSchema::new("iso", SettingKind::Number, "sensor iso; 0 for auto"),
+        SettingSchema::new(
+            "shutter",
+            SettingKind::Number,
+            "shutter speed in us; 0 for auto",
+        ),
+        SettingSchema::new("awb", SettingKind::Text, "white balance mode"),
+        SettingSchema::new(
+            "awb_gains",
+            SettingKind::Text,
+            "red,blue gains when awb is off",
+        ),
+        SettingSchema::new("exposure", SettingKind::Text, "exposure mode"),
+        SettingSchema::new("metering", SettingKind::Text, "metering mode"),
+        SettingSchema::new("drc", SettingKind::Text, "dynamic range compression"),
+        SettingSchema::new("hflip", SettingKind::Flag, "flip horizontally"),
+        SettingSchema::new("vflip", SettingKind::Flag, "flip vertically"),
+        SettingSchema::new("rotation", SettingKind::Number, "rotation in degrees"),
+    ]
+}
+
+fn http_schema() -> Vec<SettingSchema> {
+    vec![
+        SettingSchema::new(
+            "http_url",
+            SettingKind::Text,
+            "snapshot url, eg. http://camera/snap.jpg",
+        )
+        .required(),
+        SettingSchema::new(
+            "http_username",
+            SettingKind::Text,
+            "basic or digest auth username",
+        ),
+        SettingSchema::new(
+            "http_password",
+            SettingKind::Text,
+            "basic or digest auth password",
+        ),
+        SettingSchema::new("http_timeout", SettingKind::Number, "request timeout in ms"),
+    ]
+}
+
+fn rtsp_schema() -> Vec<SettingSchema> {
+    vec![
+        SettingSchema::new(
+            "rtsp_url",
+            SettingKind::Text,
+            "stream url, eg. rtsp://camera/stream",
+        )
+        .required(),
+        SettingSchema::new(
+            "resolution",
+            SettingKind::Resolution,
+            "stream resolution, eg. 1920x1080",
+        )
+        .required(),
+        SettingSchema::new(
+            "rtsp_transport",
+            SettingKind::Text,
+            "tcp or udp; defaults to tcp",
+        ),
+        SettingSchema::new(
+            "rtsp_timeout",
+            SettingKind::Number,
+            "connection timeout in ms",
+        ),
+    ]
+}
+
+#[cfg(feature = "pi")]
+fn create_pi_camera() -> Result<Box<dyn CameraLike + 'static>, HardwareError> {
+    Ok(Box::new(PiCamera::new()))
+}
+
+#[cfg(not(feature = "pi"))]
+fn create_pi_camera() -> Result<Box<dyn CameraLike + 'static>, HardwareError> {
+    Err(HardwareError::InvalidSettings(
+        "the pi camera backend requires building with the 'pi' feature".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CameraRegistry, SettingKind, SettingSchema};
+    use crate::hardware::{CameraLike, Frame, HardwareError};
+    use crate::resources::ConfigMap;
+
+    struct NullCamera {}
+
+    impl CameraLike for NullCamera {
+        fn initialize(&mut self, _config: ConfigMap) -> Result<(), HardwareError> {
+            Ok(())
+        }
+
+        fn shutdown(&mut self) -> Result<(), HardwareError> {
+            Ok(())
+        }
+
+        fn next(&mut self) -> Result<Frame, HardwareError> {
+            Err(HardwareError::NotImplemented)
+        }
+    }
+
+    fn registry() -> CameraRegistry {
+        let mut registry = CameraRegistry::new();
+        registry.register(
+            "null",
+            vec![
+                SettingSchema::new("null_size", SettingKind::Resolution, "frame size").required(),
+                SettingSchema::new("null_fast", SettingKind::Flag, "go faster"),
+            ],
+            || Ok(Box::new(NullCamera {})),
+        );
+        registry
+    }
+
+    #[test]
+    pub fn test_create_registered_backend() {
+        let mut config = ConfigMap::new();
+        config.set("null_size", "640x480");
+        assert!(registry().create("null", &config).is_ok());
+        assert!(registry().create("missing", &config).is_err());
+    }
+
+    #[test]
+    pub fn test_schema_rejects_invalid_settings() {
+        let config = ConfigMap::new();
+        assert!(registry().create("null", &config).is_err());
+
+        let mut config = ConfigMap::new();
+        config.set("null_size", "640x480");
+        config.set("null_fast", "very");
+        assert!(registry().create("null", &config).is_err());
+    }
+}
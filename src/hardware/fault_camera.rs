@@ -0,0 +1,355 @@
+use crate::encoding::Encoding;
+use crate::hardware::error::HardwareError;
+use crate::hardware::{as_resolution_tuple, xorshift, CameraLike, Frame};
+use crate::resources::ConfigMap;
+use image::imageops::FilterType;
+use image::RgbImage;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+const DEFAULT_KINDS: &str = "latency,error:device_failed,error:device_lost,corrupt,truncate,resize";
+
+/// A single thing that can go wrong with a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    None,
+    /// Delay the read by this many ms; 0 uses `fault_latency`
+    Latency(u64),
+    Error(String),
+    /// Overwrite random bytes in the frame
+    Corrupt,
+    /// Drop a random number of rows from the bottom of the frame
+    Truncate,
+    /// Return the frame at a different size; (0, 0) is half size
+    Resize(u32, u32),
+}
+
+impl FromStr for Fault {
+    type Err = HardwareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let (name, arg) = match s.find(':') {
+            Some(offset) => (&s[..offset], Some(&s[offset + 1..])),
+            None => (s.as_str(), None),
+        };
+        let invalid = || HardwareError::InvalidSettings(format!("{} is not a valid fault", s));
+        match (name, arg) {
+            ("ok", None) | ("none", None) => Ok(Fault::None),
+            ("latency", None) => Ok(Fault::Latency(0)),
+            ("latency", Some(ms)) => Ok(Fault::Latency(
+                str::parse::<u64>(ms).map_err(|_| invalid())?,
+            )),
+            ("error", Some(kind)) => {
+                as_error(kind, "")?;
+                Ok(Fault::Error(kind.to_string()))
+            }
+            ("corrupt", None) => Ok(Fault::Corrupt),
+            ("truncate", None) => Ok(Fault::Truncate),
+            ("resize", None) => Ok(Fault::Resize(0, 0)),
+            ("resize", Some(size)) => {
+                let (width, height) = as_resolution_tuple(size)?;
+                Ok(Fault::Resize(width, height))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Map a fault error name to the HardwareError it produces
+fn as_error(kind: &str, message: &str) -> Result<HardwareError, HardwareError> {
+    let message = message.to_string();
+    match kind {
+        "not_implemented" => Ok(HardwareError::NotImplemented),
+        "device_lost" => Ok(HardwareError::DeviceNoLongerAvailable(message)),
+        "encode" => Ok(HardwareError::FailedToEncodeFrame(message)),
+        "invalid_settings" => Ok(HardwareError::InvalidSettings(message)),
+        "device_failed" => Ok(HardwareError::DeviceFailed(message)),
+        "io" => Ok(HardwareError::IoError(message)),
+        _ => Err(HardwareError::InvalidSettings(format!(
+            "{} is not a valid fault error; use not_implemented, device_lost, encode, \
+             invalid_settings, device_failed or io",
+            kind
+        ))),
+    }
+}
+
+/// When and which faults to inject; either a scripted list applied one per frame, or at
+/// random with a given probability per frame, reproducible for a given seed.
+#[derive(Debug, Clone)]
+pub struct FaultPlan {
+    pub script: Vec<Fault>,
+    pub repeat: bool,
+    pub rate: f32,
+    pub kinds: Vec<Fault>,
+    pub latency: u64,
+    pub seed: u64,
+}
+
+impl Default for FaultPlan {
+    fn default() -> Self {
+        FaultPlan {
+            script: Vec::new(),
+            repeat: false,
+            rate: 0f32,
+            kinds: Vec::new(),
+            latency: 1000,
+            seed: 1,
+        }
+    }
+}
+
+impl FaultPlan {
+    /// Read the fault settings; returns None if no faults are configured.
+    pub fn from_config(config: &ConfigMap) -> Result<Option<FaultPlan>, HardwareError> {
+        let script = config.get_string("fault_plan");
+        let rate = config.get_string("fault_rate");
+        if script.is_none() && rate.is_none() {
+            return Ok(None);
+        }
+        let mut plan = FaultPlan {
+            repeat: config.flag("fault_plan_repeat"),
+            latency: config.get_u32("fault_latency").unwrap_or(1000) as u64,
+            seed: config.get_u32("fault_seed").unwrap_or(1).max(1) as u64,
+            ..Default::default()
+        };
+        if let Some(script) = script {
+            plan.script = parse_faults(&script)?;
+        }
+        if let Some(rate) = rate {
+            plan.rate = match str::parse::<f32>(&rate) {
+                Ok(r) if (0f32..=1f32).contains(&r) => r,
+                _ => {
+                    return Err(HardwareError::InvalidSettings(format!(
+                        "{} is not a valid fault_rate; use a probability from 0 to 1",
+                        rate
+                    )));
+                }
+            };
+            plan.kinds = parse_faults(
+                &config
+                    .get_string("fault_kinds")
+                    .unwrap_or_else(|| DEFAULT_KINDS.to_string()),
+            )?;
+        }
+        Ok(Some(plan))
+    }
+}
+
+fn parse_faults(value: &str) -> Result<Vec<Fault>, HardwareError> {
+    value
+        .split(',')
+        .filter(|v| !v.trim().is_empty())
+        .map(Fault::from_str)
+        .collect()
+}
+
+/// Wraps another camera and injects faults into it, to test how the capture loop recovers.
+pub struct FaultyCamera {
+    inner: Box<dyn CameraLike + 'static>,
+    plan: FaultPlan,
+    seed: u64,
+    frame: usize,
+    active: Option<RgbImage>,
+}
+
+impl FaultyCamera {
+    pub fn new(inner: Box<dyn CameraLike + 'static>, plan: FaultPlan) -> FaultyCamera {
+        FaultyCamera {
+            seed: plan.seed,
+            inner,
+            plan,
+            frame: 0,
+            active: None,
+        }
+    }
+
+    fn next_fault(&mut self) -> Fault {
+        let index = self.frame;
+        self.frame += 1;
+        if !self.plan.script.is_empty() {
+            if index < self.plan.script.len() {
+                return self.plan.script[index].clone();
+            }
+            if self.plan.repeat {
+                return self.plan.script[index % self.plan.script.len()].clone();
+            }
+        }
+        if self.plan.kinds.is_empty() || self.plan.rate <= 0f32 {
+            return Fault::None;
+        }
+        let roll = (xorshift(&mut self.seed) % 10000) as f32 / 10000f32;
+        if roll >= self.plan.rate {
+            return Fault::None;
+        }
+        let choice = (xorshift(&mut self.seed) % self.plan.kinds.len() as u64) as usize;
+        self.plan.kinds[choice].clone()
+    }
+
+    fn damage(&mut self, fault: &Fault, image: RgbImage) -> RgbImage {
+        match fault {
+            Fault::Corrupt if !image.is_empty() => {
+                let (width, height) = image.dimensions();
+                let mut raw = image.into_raw();
+                for _ in 0..(raw.len() / 100).max(1) {
+                    let offset = (xorshift(&mut self.seed) % raw.len() as u64) as usize;
+                    raw[offset] = xorshift(&mut self.seed) as u8;
+                }
+                RgbImage::from_raw(width, height, raw).unwrap_or_default()
+            }
+            Fault::Truncate if image.height() > 0 => {
+                let height = (xorshift(&mut self.seed) % image.height() as u64) as u32;
+                let width = image.width();
+                let mut raw = image.into_raw();
+                raw.truncate((width * height.max(1) * 3) as usize);
+                RgbImage::from_raw(width, height.max(1), raw).unwrap_or_default()
+            }
+            Fault::Resize(width, height) => {
+                let (width, height) = if *width == 0 || *height == 0 {
+                    ((image.width() / 2).max(1), (image.height() / 2).max(1))
+                } else {
+                    (*width, *height)
+                };
+                image::imageops::resize(&image, width, height, FilterType::Nearest)
+            }
+            _ => image,
+        }
+    }
+}
+
+impl CameraLike for FaultyCamera {
+    fn initialize(&mut self, config: ConfigMap) -> Result<(), HardwareError> {
+        self.seed = self.plan.seed;
+        self.frame = 0;
+        self.inner.initialize(config)
+    }
+
+    fn shutdown(&mut self) -> Result<(), HardwareError> {
+        self.active = None;
+        self.inner.shutdown()
+    }
+
+    fn next(&mut self) -> Result<Frame, HardwareError> {
+        let index = self.frame;
+        let fault = self.next_fault();
+        match &fault {
+            Fault::Latency(ms) => {
+                let ms = if *ms == 0 { self.plan.latency } else { *ms };
+                thread::sleep(Duration::from_millis(ms));
+            }
+            Fault::Error(kind) => {
+                return Err(as_error(
+                    kind,
+                    &format!("Injected fault on frame {}", index),
+                )?);
+            }
+            _ => {}
+        }
+
+        let image = {
+            let frame = self.inner.next()?;
+            RgbImage::from_raw(frame.width(), frame.height(), frame.as_raw().to_vec())
+        };
+        let image = match image {
+            Some(i) => self.damage(&fault, i),
+            None => {
+                return Err(HardwareError::DeviceFailed(
+                    "Invalid frame from wrapped camera".to_string(),
+                ));
+            }
+        };
+        self.active = Some(image);
+        match self.active.as_ref() {
+            Some(image) => Ok(Encoding::new().frame_from_slice(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+            )?),
+            None => Err(HardwareError::DeviceNoLongerAvailable(
+                "Invalid faulty frame".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fault, FaultPlan, FaultyCamera};
+    use crate::hardware::pattern_camera::PatternCamera;
+    use crate::hardware::{CameraLike, HardwareError};
+    use crate::resources::ConfigMap;
+    use image::RgbImage;
+
+    fn camera(settings: &[(&str, &str)]) -> FaultyCamera {
+        let mut config = ConfigMap::new();
+        config.set("resolution", "64x48");
+        for (key, value) in settings.iter() {
+            config.set(key, value);
+        }
+        let plan = FaultPlan::from_config(&config).unwrap().unwrap();
+        let mut camera = FaultyCamera::new(Box::new(PatternCamera::new()), plan);
+        camera.initialize(config).unwrap();
+        camera
+    }
+
+    #[test]
+    pub fn test_no_plan_without_settings() {
+        assert!(FaultPlan::from_config(&ConfigMap::new()).unwrap().is_none());
+        let mut config = ConfigMap::new();
+        config.set("fault_plan", "ok,explode");
+        assert!(FaultPlan::from_config(&config).is_err());
+    }
+
+    #[test]
+    pub fn test_scripted_plan() {
+        let mut camera = camera(&[(
+            "fault_plan",
+            "ok,error:device_lost,resize:32x16,truncate,corrupt,latency:1",
+        )]);
+        assert_eq!(camera.next().unwrap().width(), 64);
+        match camera.next() {
+            Err(HardwareError::DeviceNoLongerAvailable(_)) => {}
+            _ => panic!("expected the device to be lost"),
+        }
+        assert_eq!(camera.next().unwrap().dimensions(), (32, 16));
+        assert!(camera.next().unwrap().height() < 48);
+        assert_eq!(camera.next().unwrap().dimensions(), (64, 48));
+        assert!(camera.next().is_ok());
+        assert!(camera.next().is_ok());
+    }
+
+    #[test]
+    pub fn test_random_plan_is_reproducible() {
+        let outcomes = |seed: &str| {
+            let mut camera = camera(&[
+                ("fault_rate", "0.5"),
+                ("fault_kinds", "error:io,resize"),
+                ("fault_seed", seed),
+            ]);
+            (0..32)
+                .map(|_| camera.next().map(|f| f.width()).unwrap_or(0))
+                .collect::<Vec<u32>>()
+        };
+        let first = outcomes("7");
+        assert_eq!(first, outcomes("7"));
+        assert!(first.contains(&0));
+        assert!(first.contains(&32));
+        assert!(first.contains(&64));
+    }
+
+    #[test]
+    pub fn test_damage_empty_frames() {
+        let mut camera = camera(&[("fault_plan", "error:io")]);
+        for fault in [Fault::Corrupt, Fault::Truncate].iter() {
+            let image = camera.damage(fault, RgbImage::new(0, 0));
+            assert_eq!(image.dimensions(), (0, 0));
+        }
+        match camera.next() {
+            Err(HardwareError::IoError(message)) => {
+                assert_eq!(message, "Injected fault on frame 0")
+            }
+            _ => panic!("expected an injected io error"),
+        }
+    }
+}
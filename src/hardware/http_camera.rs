@@ -0,0 +1,120 @@
+use crate::encoding::Encoding;
+use crate::hardware::error::HardwareError;
+use crate::hardware::http_client::HttpClient;
+use crate::hardware::{CameraLike, Frame};
+use crate::resources::ConfigMap;
+use std::time::Duration;
+
+/// Fetches a still from the http snapshot url of a network camera for every frame.
+#[derive(Default)]
+pub struct HttpCamera {
+    client: Option<HttpClient>,
+    active: Option<(Vec<u8>, u32, u32)>,
+}
+
+impl HttpCamera {
+    pub fn new() -> HttpCamera {
+        Default::default()
+    }
+}
+
+impl CameraLike for HttpCamera {
+    fn initialize(&mut self, config: ConfigMap) -> Result<(), HardwareError> {
+        let url = match config.get_string("http_url") {
+            Some(u) => u,
+            None => {
+                return Err(HardwareError::InvalidSettings(
+                    "http_url is required for the http camera".to_string(),
+                ));
+            }
+        };
+        let timeout = Duration::from_millis(config.get_u32("http_timeout").unwrap_or(10000) as u64);
+        let mut client = HttpClient::new(&url, timeout)?;
+        if let Some(username) = config.get_string("http_username") {
+            let password = config.get_string("http_password").unwrap_or_default();
+            client = client.credentials(&username, &password);
+        }
+        self.client = Some(client);
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), HardwareError> {
+        self.client = None;
+        self.active = None;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Frame, HardwareError> {
+        let client = match self.client.as_mut() {
+            Some(c) => c,
+            None => {
+                return Err(HardwareError::DeviceNoLongerAvailable(
+                    "Device state is invalid; call initialize() first".to_string(),
+                ));
+            }
+        };
+        let response = client.get()?;
+        let img = image::load_from_memory(&response.body)?.to_rgb8();
+        let (width, height) = (img.width(), img.height());
+        self.active = Some((img.into_raw(), width, height));
+        match self.active.as_ref() {
+            Some((buffer, width, height)) => {
+                Ok(Encoding::new().frame_from_slice(buffer, *width, *height)?)
+            }
+            None => Err(HardwareError::DeviceNoLongerAvailable(
+                "Invalid http frame".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpCamera;
+    use crate::hardware::test_server::{TestAuth, TestServer};
+    use crate::hardware::CameraLike;
+    use crate::resources::ConfigMap;
+
+    fn config(server: &TestServer, username: &str, password: &str) -> ConfigMap {
+        let mut config = ConfigMap::new();
+        config.set("http_url", server.url("/snapshot.png"));
+        config.set("http_username", username);
+        config.set("http_password", password);
+        config
+    }
+
+    #[test]
+    pub fn test_fetch_with_basic_auth() {
+        let server = TestServer::start(TestAuth::Basic("admin", "secret"));
+        let mut camera = HttpCamera::new();
+        camera
+            .initialize(config(&server, "admin", "secret"))
+            .unwrap();
+        let frame = camera.next().unwrap();
+        assert_eq!(frame.width(), 256);
+        assert_eq!(frame.height(), 256);
+        camera.shutdown().unwrap();
+    }
+
+    #[test]
+    pub fn test_fetch_with_digest_auth() {
+        let server = TestServer::start(TestAuth::Digest("admin", "secret"));
+        let mut camera = HttpCamera::new();
+        camera
+            .initialize(config(&server, "admin", "secret"))
+            .unwrap();
+        assert!(camera.next().is_ok());
+        assert!(camera.next().is_ok());
+        camera.shutdown().unwrap();
+    }
+
+    #[test]
+    pub fn test_fetch_with_bad_credentials() {
+        let server = TestServer::start(TestAuth::Digest("admin", "secret"));
+        let mut camera = HttpCamera::new();
+        camera
+            .initialize(config(&server, "admin", "wrong"))
+            .unwrap();
+        assert!(camera.next().is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::hardware::error::HardwareError;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The largest response body accepted from a camera, in bytes
+const MAX_BODY: usize = 64 * 1024 * 1024;
+
+/// A minimal blocking http/1.1 client; enough to fetch stills from a network camera.
+/// Only plain http is supported, with either basic or digest authentication.
+pub struct HttpClient {
+    host: String,
+    port: u16,
+    path: String,
+    credentials: Option<(String, String)>,
+    timeout: Duration,
+    nonce_count: u32,
+}
+
+pub struct HttpResponse {
+    pub status: u32,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpClient {
+    pub fn new(url: &str, timeout: Duration) -> Result<HttpClient, HardwareError> {
+        let rest = match url.strip_prefix("http://") {
+            Some(r) => r,
+            None => {
+                return Err(HardwareError::InvalidSettings(format!(
+                    "{} is not a valid url; only http:// urls are supported",
+                    url
+                )));
+            }
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(offset) => (&rest[..offset], &rest[offset..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(offset) => {
+                let port = str::parse::<u16>(&authority[offset + 1..]).map_err(|_| {
+                    HardwareError::InvalidSettings(format!("{} has an invalid port", url))
+                })?;
+                (&authority[..offset], port)
+            }
+            None => (authority, 80),
+        };
+        Ok(HttpClient {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            credentials: None,
+            timeout,
+            nonce_count: 0,
+        })
+    }
+
+    pub fn credentials(mut self, username: &str, password: &str) -> HttpClient {
+        self.credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Fetch the url; if the server asks for authentication, retry once with the credentials
+    /// using whichever scheme (basic or digest) the server asked for.
+    pub fn get(&mut self) -> Result<HttpResponse, HardwareError> {
+        let response = self.request(None)?;
+        if response.status != 401 || self.credentials.is_none() {
+            return self.as_success(response);
+        }
+
+        let challenge = response
+            .headers
+            .get("www-authenticate")
+            .cloned()
+            .unwrap_or_default();
+        let authorization = if challenge.to_lowercase().starts_with("digest") {
+            self.digest_authorization(&challenge)?
+        } else {
+            let (username, password) = self.credentials.clone().unwrap_or_default();
+            basic_authorization(&username, &password)
+        };
+        let response = self.request(Some(&authorization))?;
+        self.as_success(response)
+    }
+
+    fn as_success(&self, response: HttpResponse) -> Result<HttpResponse, HardwareError> {
+        match response.status {
+            200..=299 => Ok(response),
+            401 | 403 => Err(HardwareError::InvalidSettings(format!(
+                "http://{}:{}{} rejected the credentials with status {}",
+                self.host, self.port, self.path, response.status
+            ))),
+            status => Err(HardwareError::DeviceFailed(format!(
+                "http://{}:{}{} returned status {}",
+                self.host, self.port, self.path, status
+            ))),
+        }
+    }
+
+    fn request(&self, authorization: Option<&str>) -> Result<HttpResponse, HardwareError> {
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| {
+                HardwareError::DeviceNoLongerAvailable(format!("unable to resolve {}", self.host))
+            })?;
+        let mut stream = TcpStream::connect_timeout(&address, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+
+        let mut request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}:{}\r\nUser-Agent: rust-snapshot\r\nConnection: close\r\n",
+            self.path, self.host, self.port
+        );
+        if let Some(value) = authorization {
+            request.push_str(&format!("Authorization: {}\r\n", value));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+
+        read_response(BufReader::new(stream))
+    }
+
+    fn digest_authorization(&mut self, challenge: &str) -> Result<String, HardwareError> {
+        let (username, password) = self.credentials.clone().unwrap_or_default();
+        let params = parse_challenge(&challenge["digest".len()..]);
+        let realm = params.get("realm").cloned().unwrap_or_default();
+        let nonce = match params.get("nonce") {
+            Some(n) => n.clone(),
+            None => {
+                return Err(HardwareError::DeviceFailed(
+                    "digest challenge is missing a nonce".to_string(),
+                ));
+            }
+        };
+
+        self.nonce_count += 1;
+        let nc = format!("{:08x}", self.nonce_count);
+        let cnonce = format!(
+            "{:x}",
+            md5::compute(format!(
+                "{:?}",
+                SystemTime::now().duration_since(UNIX_EPOCH)
+            ))
+        );
+        let ha1 = format!(
+            "{:x}",
+            md5::compute(format!("{}:{}:{}", username, realm, password))
+        );
+        let ha2 = format!("{:x}", md5::compute(format!("GET:{}", self.path)));
+        let qop = params
+            .get("qop")
+            .map(|q| q.split(',').any(|v| v.trim() == "auth"))
+            .unwrap_or(false);
+        let response = if qop {
+            md5::compute(format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2))
+        } else {
+            md5::compute(format!("{}:{}:{}", ha1, nonce, ha2))
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", response=\"{:x}\"",
+            username, realm, nonce, self.path, response
+        );
+        if qop {
+            header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+        }
+        if let Some(opaque) = params.get("opaque") {
+            header.push_str(&format!(", opaque=\"{}\"", opaque));
+        }
+        Ok(header)
+    }
+}
+
+fn basic_authorization(username: &str, password: &str) -> String {
+    format!(
+        "Basic {}",
+        base64::encode(format!("{}:{}", username, password))
+    )
+}
+
+/// Parse the key="value" pairs from an authentication challenge
+pub(crate) fn parse_challenge(challenge: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut rest = challenge.trim();
+    while !rest.is_empty() {
+        let eq = match rest.find('=') {
+            Some(offset) => offset,
+            None => break,
+        };
+        let key = rest[..eq]
+            .trim()
+            .trim_start_matches(',')
+            .trim()
+            .to_lowercase();
+        rest = &rest[eq + 1..];
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let value = &quoted[..end];
+            rest = quoted.get(end + 1..).unwrap_or("");
+            value
+        } else {
+            let end = rest.find(',').unwrap_or(rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+        let _ = params.insert(key, value.trim().to_string());
+        rest = rest.trim_start().trim_start_matches(',').trim_start();
+    }
+    params
+}
+
+fn body_too_large(size: usize) -> HardwareError {
+    HardwareError::DeviceFailed(format!(
+        "response body of {} bytes or more is larger than the {} MB limit",
+        size,
+        MAX_BODY / 1024 / 1024
+    ))
+}
+
+fn read_response<T: Read>(mut reader: BufReader<T>) -> Result<HttpResponse, HardwareError> {
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|v| str::parse::<u32>(v).ok())
+        .ok_or_else(|| {
+            HardwareError::DeviceFailed(format!("invalid http response: {}", status_line.trim()))
+        })?;
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(offset) = line.find(':') {
+            let _ = headers.insert(
+                line[..offset].trim().to_lowercase(),
+                line[offset + 1..].trim().to_string(),
+            );
+        }
+    }
+
+    let mut body = Vec::new();
+    let chunked = headers
+        .get("transfer-encoding")
+        .map(|v| v.to_lowercase().contains("chunked"))
+        .unwrap_or(false);
+    if chunked {
+        loop {
+            let mut size_line = String::new();
+            reader.read_line(&mut size_line)?;
+            let size_hex = size_line.trim().split(';').next().unwrap_or("");
+            let size = usize::from_str_radix(size_hex, 16).map_err(|_| {
+                HardwareError::DeviceFailed(format!("invalid chunk size: {}", size_hex))
+            })?;
+            if size == 0 {
+                break;
+            }
+            let offset = body.len();
+            let end = offset
+                .checked_add(size)
+                .filter(|end| *end <= MAX_BODY)
+                .ok_or_else(|| body_too_large(size))?;
+            body.resize(end, 0);
+            reader.read_exact(&mut body[offset..])?;
+            let mut crlf = [0u8; 2];
+            reader.read_exact(&mut crlf)?;
+        }
+    } else if let Some(length) = headers
+        .get("content-length")
+        .and_then(|v| str::parse::<usize>(v).ok())
+    {
+        if length > MAX_BODY {
+            return Err(body_too_large(length));
+        }
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.take(MAX_BODY as u64 + 1).read_to_end(&mut body)?;
+        if body.len() > MAX_BODY {
+            return Err(body_too_large(body.len()));
+        }
+    }
+
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_challenge, read_response, HttpClient};
+    use crate::hardware::HardwareError;
+    use std::io::BufReader;
+    use std::time::Duration;
+
+    #[test]
+    pub fn test_parse_url() {
+        let client = HttpClient::new(
+            "http://camera.local:8080/snap.jpg?x=1",
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        assert_eq!(client.host, "camera.local");
+        assert_eq!(client.port, 8080);
+        assert_eq!(client.path, "/snap.jpg?x=1");
+        assert!(HttpClient::new("https://camera.local/", Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    pub fn test_parse_challenge() {
+        let params =
+            parse_challenge(r#" realm="cam, inc", qop="auth,auth-int", nonce="abc", stale=FALSE"#);
+        assert_eq!(params["realm"], "cam, inc");
+        assert_eq!(params["qop"], "auth,auth-int");
+        assert_eq!(params["nonce"], "abc");
+        assert_eq!(params["stale"], "FALSE");
+    }
+
+    #[test]
+    pub fn test_rejects_oversized_bodies() {
+        let responses: [&[u8]; 3] = [
+            b"HTTP/1.1 200 OK\r\nContent-Length: 99999999999\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\nfffffffffffffffe\r\n",
+        ];
+        for response in responses.iter() {
+            match read_response(BufReader::new(*response)) {
+                Err(HardwareError::DeviceFailed(_)) => {}
+                Err(err) => panic!("expected DeviceFailed, got {:?}", err),
+                Ok(_) => panic!("expected an oversized body to be rejected"),
+            }
+        }
+
+        let response = read_response(BufReader::new(
+            &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nab\r\n0\r\n\r\n"[..],
+        ))
+        .unwrap();
+        assert_eq!(response.body, b"ab");
+    }
+}
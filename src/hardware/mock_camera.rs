@@ -1,16 +1,39 @@
 use crate::encoding::Encoding;
 use crate::hardware::error::HardwareError;
-use crate::hardware::{CameraLike, Frame};
-use crate::resources::{ConfigMap, ResourceFolder};
+use crate::hardware::{as_resolution_tuple, CameraLike, Frame};
+use crate::resources::{ConfigMap, ResourceFolder, TimeSnapshot};
 use image::io::Reader as ImageReader;
-use std::fs::DirEntry;
-use std::path::PathBuf;
+use rust_ffmpeg_capture::{Capture, CaptureError, CaptureSettings};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+const IMAGE_GLOB: &str = "*.png,*.jpg,*.jpeg,*.bmp,*.gif,*.tif,*.tiff";
+
+/// Where the mock frames come from; a folder of images or a video file decoded by libav.
+enum MockSource {
+    Folder(Vec<PathBuf>),
+    Video {
+        settings: CaptureSettings,
+        capture: Option<Capture>,
+        buffer: Vec<u8>,
+
+        /// Frames read since the video was last opened
+        frames: u32,
+    },
+}
 
 pub struct MockCamera {
     offset: isize,
     repeat: bool,
-    frames: Vec<DirEntry>,
+    source: MockSource,
     active: Option<Vec<u8>>,
+    realtime: bool,
+    speed: f32,
+    last_timestamp: Option<u128>,
+    fail_after: Option<u32>,
+    delay: u64,
+    served: u32,
 }
 
 impl Default for MockCamera {
@@ -18,8 +41,14 @@ impl Default for MockCamera {
         MockCamera {
             repeat: false,
             offset: -1,
-            frames: Vec::new(),
+            source: MockSource::Folder(Vec::new()),
             active: None,
+            realtime: false,
+            speed: 1f32,
+            last_timestamp: None,
+            fail_after: None,
+            delay: 0,
+            served: 0,
         }
     }
 }
@@ -45,35 +74,303 @@ impl MockCamera {
             ))
         }
     }
+
+    fn next_file(&mut self) -> Result<PathBuf, HardwareError> {
+        let frames = match &self.source {
+            MockSource::Folder(frames) => frames,
+            MockSource::Video { .. } => return Err(HardwareError::NotImplemented),
+        };
+        self.offset += 1;
+        if self.offset >= (frames.len() as isize) {
+            if self.repeat && !frames.is_empty() {
+                self.offset = 0;
+                self.last_timestamp = None;
+            } else {
+                return Err(HardwareError::DeviceNoLongerAvailable(
+                    "Ran out of mock frames".to_string(),
+                ));
+            }
+        }
+        Ok(frames[self.offset as usize].clone())
+    }
+
+    fn next_video_frame(&mut self) -> Result<Frame, HardwareError> {
+        let repeat = self.repeat;
+        let (settings, capture, buffer, frames) = match &mut self.source {
+            MockSource::Video {
+                settings,
+                capture,
+                buffer,
+                frames,
+            } => (settings, capture, buffer, frames),
+            MockSource::Folder(_) => return Err(HardwareError::NotImplemented),
+        };
+        loop {
+            if capture.is_none() {
+                let mut opened = Capture::new(settings.clone());
+                opened.init()?;
+                *capture = Some(opened);
+                *frames = 0;
+            }
+            let result = match capture.as_mut() {
+                Some(c) => c.read(buffer),
+                None => continue,
+            };
+            match result {
+                Ok(_) => {
+                    *frames += 1;
+                    break;
+                }
+                Err(CaptureError::EndOfStream) if *frames == 0 => {
+                    return Err(HardwareError::DeviceNoLongerAvailable(format!(
+                        "Mock video {} has no frames",
+                        settings.device
+                    )));
+                }
+                Err(CaptureError::EndOfStream) if repeat => {
+                    if let Some(c) = capture.take() {
+                        c.shutdown();
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(Encoding::new().frame_from_slice(
+            buffer.as_slice(),
+            settings.resolution.0,
+            settings.resolution.1,
+        )?)
+    }
+
+    /// Sleep for the gap between the original capture times of this frame and the last one.
+    /// Frames written by snapshot are named `{timestamp in ms}-{date}.png`.
+    fn wait_for_timestamp(&mut self, path: &Path) {
+        let timestamp = TimeSnapshot::from_frame_path(path).map(|t| t.timestamp);
+        if let (Some(last), Some(current)) = (self.last_timestamp, timestamp) {
+            if current > last {
+                let delay = (current - last) as f32 / self.speed;
+                thread::sleep(Duration::from_millis(delay as u64));
+            }
+        }
+        if timestamp.is_some() {
+            self.last_timestamp = timestamp;
+        }
+    }
 }
 
 impl CameraLike for MockCamera {
     fn initialize(&mut self, config: ConfigMap) -> Result<(), HardwareError> {
-        if let Some(path) = config.get_string("use_mock_folder") {
+        self.repeat = config.flag("use_mock_repeat_frames");
+        self.realtime = config.flag("use_mock_realtime");
+        self.speed = config
+            .get_string("use_mock_speed")
+            .and_then(|v| str::parse::<f32>(&v).ok())
+            .filter(|v| *v > 0f32)
+            .unwrap_or(1f32);
+        self.fail_after = config.get_u32("use_mock_fail_after");
+        self.delay = config.get_u32("use_mock_delay").unwrap_or(0) as u64;
+        self.offset = -1;
+        self.served = 0;
+        self.last_timestamp = None;
+
+        if let Some(path) = config.get_string("use_mock_video") {
+            let resolution = config
+                .get_string("use_mock_resolution")
+                .or_else(|| config.get_string("resolution"))
+                .unwrap_or_else(|| "640x480".to_string());
+            let resolution = as_resolution_tuple(&resolution)?;
+            self.source = MockSource::Video {
+                settings: CaptureSettings {
+                    backend: String::new(),
+                    device: path,
+                    framerate: 1,
+                    resolution,
+                    pixel_format: String::new(),
+                    options: Vec::new(),
+                },
+                capture: None,
+                buffer: vec![0u8; (resolution.0 * resolution.1 * 3) as usize],
+                frames: 0,
+            };
+        } else if let Some(path) = config.get_string("use_mock_folder") {
             let resources = ResourceFolder::new(&path).require_existing()?;
-            self.frames = resources.enumerate_files()?;
-            self.repeat = config.flag("use_mock_repeat_frames");
-            self.offset = -1;
+            let glob = config
+                .get_string("use_mock_glob")
+                .unwrap_or_else(|| IMAGE_GLOB.to_string());
+            let frames = resources
+                .walk_files()?
+                .into_iter()
+                .filter(
+                    |path| match path.file_name().and_then(|name| name.to_str()) {
+                        Some(name) => glob.split(',').any(|p| glob_matches(p.trim(), name)),
+                        None => false,
+                    },
+                )
+                .collect();
+            self.source = MockSource::Folder(frames);
         }
         Ok(())
     }
 
     fn shutdown(&mut self) -> Result<(), HardwareError> {
+        if let MockSource::Video { capture, .. } = &mut self.source {
+            if let Some(c) = capture.take() {
+                c.shutdown();
+            }
+        }
         Ok(())
     }
 
     fn next(&mut self) -> Result<Frame, HardwareError> {
-        self.offset += 1;
-        if self.offset >= (self.frames.len() as isize) {
-            if self.repeat {
-                self.offset = 0;
-            } else {
-                return Err(HardwareError::DeviceNoLongerAvailable(
-                    "Ran out of mock frames".to_string(),
-                ));
+        if let Some(limit) = self.fail_after {
+            if self.served >= limit {
+                return Err(HardwareError::DeviceNoLongerAvailable(format!(
+                    "Scripted failure after {} mock frames",
+                    limit
+                )));
             }
         }
-        let entry_path = self.frames[(self.offset as usize)].path();
+        self.served += 1;
+        if self.delay > 0 {
+            thread::sleep(Duration::from_millis(self.delay));
+        }
+
+        if let MockSource::Video { .. } = self.source {
+            return self.next_video_frame();
+        }
+        let entry_path = self.next_file()?;
+        if self.realtime {
+            self.wait_for_timestamp(&entry_path);
+        }
         Ok(self.read_frame(entry_path)?)
     }
 }
+
+/// Match a filename against a glob with `*` (any run of characters) and `?` (any one character).
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_matches, MockCamera};
+    use crate::hardware::CameraLike;
+    use crate::resources::ConfigMap;
+    use std::fs;
+    use std::time::Instant;
+
+    fn config() -> ConfigMap {
+        let mut config = ConfigMap::new();
+        config.set("use_mock_folder", "test/data/frames");
+        config
+    }
+
+    fn count_frames(camera: &mut MockCamera) -> usize {
+        let mut count = 0;
+        while camera.next().is_ok() {
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    pub fn test_glob_matches() {
+        assert!(glob_matches("*.png", "frame_00000001.PNG"));
+        assert!(glob_matches("frame_0000000?.png", "frame_00000001.png"));
+        assert!(glob_matches("*_*1.png", "frame_00000011.png"));
+        assert!(!glob_matches("*.png", "frame.png.txt"));
+        assert!(!glob_matches("frame_0000000?.png", "frame_00000011.png"));
+    }
+
+    #[test]
+    pub fn test_filter_folder_by_glob() {
+        let mut config = config();
+        config.set("use_mock_glob", "frame_0000000?.png, frame_00000015.png");
+        let mut camera = MockCamera::new();
+        camera.initialize(config).unwrap();
+        assert_eq!(count_frames(&mut camera), 11);
+    }
+
+    #[test]
+    pub fn test_scripted_failure() {
+        let mut config = config();
+        config.set("use_mock_repeat_frames", "1");
+        config.set("use_mock_fail_after", "20");
+        let mut camera = MockCamera::new();
+        camera.initialize(config).unwrap();
+        assert_eq!(count_frames(&mut camera), 20);
+    }
+
+    #[test]
+    pub fn test_slow_reads() {
+        let mut config = config();
+        config.set("use_mock_delay", "50");
+        let mut camera = MockCamera::new();
+        camera.initialize(config).unwrap();
+        let start = Instant::now();
+        camera.next().unwrap();
+        camera.next().unwrap();
+        assert!(start.elapsed().as_millis() >= 100);
+    }
+
+    #[test]
+    pub fn test_realtime_playback() {
+        let folder = "test/output/mock_realtime";
+        let _ = fs::remove_dir_all(folder);
+        fs::create_dir_all(folder).unwrap();
+        let frame = fs::read("test/data/frames/frame_00000000.png").unwrap();
+        for t in [1000, 1200, 1400].iter() {
+            fs::write(format!("{}/{}-frame.png", folder, t), &frame).unwrap();
+        }
+
+        let mut config = ConfigMap::new();
+        config.set("use_mock_folder", folder);
+        config.set("use_mock_realtime", "1");
+        config.set("use_mock_speed", "2");
+        let mut camera = MockCamera::new();
+        camera.initialize(config).unwrap();
+        let start = Instant::now();
+        assert_eq!(count_frames(&mut camera), 3);
+        assert!(start.elapsed().as_millis() >= 200);
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    /// libav reads a single png as a video with one frame
+    #[test]
+    pub fn test_video_playback() {
+        let mut config = ConfigMap::new();
+        config.set("use_mock_video", "test/data/frames/frame_00000000.png");
+        config.set("use_mock_resolution", "256x256");
+        let mut camera = MockCamera::new();
+        camera.initialize(config.clone()).unwrap();
+        assert_eq!(camera.next().unwrap().width(), 256);
+        assert!(camera.next().is_err());
+        camera.shutdown().unwrap();
+
+        config.set("use_mock_repeat_frames", "1");
+        config.set("use_mock_fail_after", "3");
+        let mut camera = MockCamera::new();
+        camera.initialize(config).unwrap();
+        assert_eq!(count_frames(&mut camera), 3);
+        camera.shutdown().unwrap();
+    }
+}
@@ -0,0 +1,243 @@
+use crate::encoding::{BitmapFont, Encoding};
+use crate::hardware::error::HardwareError;
+use crate::hardware::{as_resolution_tuple, xorshift, CameraLike, Frame};
+use crate::resources::ConfigMap;
+use image::{Rgb, RgbImage};
+use std::str::FromStr;
+
+const BARS: [[u8; 3]; 8] = [
+    [255, 255, 255],
+    [255, 255, 0],
+    [0, 255, 255],
+    [0, 255, 0],
+    [255, 0, 255],
+    [255, 0, 0],
+    [0, 0, 255],
+    [0, 0, 0],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    Bars,
+    Gradient,
+    Shapes,
+}
+
+impl FromStr for Pattern {
+    type Err = HardwareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bars" => Ok(Pattern::Bars),
+            "gradient" => Ok(Pattern::Gradient),
+            "shapes" => Ok(Pattern::Shapes),
+            _ => Err(HardwareError::InvalidSettings(format!(
+                "{} is not a valid pattern; use bars, gradient or shapes",
+                s
+            ))),
+        }
+    }
+}
+
+/// Generates synthetic frames so the capture loop can be exercised without a device or fixtures.
+pub struct PatternCamera {
+    pattern: Pattern,
+    resolution: (u32, u32),
+    noise: u8,
+    timestamp: bool,
+    seed: u64,
+    frame: u64,
+    active: Option<RgbImage>,
+}
+
+impl Default for PatternCamera {
+    fn default() -> Self {
+        PatternCamera {
+            pattern: Pattern::Bars,
+            resolution: (640, 480),
+            noise: 0,
+            timestamp: false,
+            seed: 1,
+            frame: 0,
+            active: None,
+        }
+    }
+}
+
+impl PatternCamera {
+    pub fn new() -> PatternCamera {
+        Default::default()
+    }
+
+    fn render(&mut self) -> RgbImage {
+        let (width, height) = self.resolution;
+        let mut image = match self.pattern {
+            Pattern::Bars => RgbImage::from_fn(width, height, |x, _| {
+                Rgb(BARS[(x * BARS.len() as u32 / width) as usize])
+            }),
+            Pattern::Gradient => RgbImage::from_fn(width, height, |x, y| {
+                let offset = self.frame as u32;
+                Rgb([
+                    (((x + offset) % width) * 255 / width) as u8,
+                    (y * 255 / height) as u8,
+                    ((offset * 4) % 256) as u8,
+                ])
+            }),
+            Pattern::Shapes => self.render_shapes(),
+        };
+
+        if self.noise > 0 {
+            let amplitude = self.noise as i32;
+            for pixel in image.pixels_mut() {
+                for channel in pixel.0.iter_mut() {
+                    let delta =
+                        (xorshift(&mut self.seed) % (2 * amplitude as u64 + 1)) as i32 - amplitude;
+                    *channel = (*channel as i32 + delta).clamp(0, 255) as u8;
+                }
+            }
+        }
+
+        if self.timestamp {
+            let text = format!(
+                "{} #{}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                self.frame
+            );
+            let font = BitmapFont::with_scale((height / 160).max(1));
+            let (text_width, text_height) = font.measure(&text);
+            let margin = font.measure("0").1 / 2;
+            for y in 0..(text_height + 2 * margin).min(height) {
+                for x in 0..(text_width + 2 * margin).min(width) {
+                    image.put_pixel(x, y, Rgb([0, 0, 0]));
+                }
+            }
+            font.draw(
+                &mut image,
+                &text,
+                margin as i64,
+                margin as i64,
+                Rgb([255, 255, 255]),
+            );
+        }
+
+        image
+    }
+
+    /// A square sliding left to right and a circle moving top to bottom over a dark background
+    fn render_shapes(&self) -> RgbImage {
+        let (width, height) = self.resolution;
+        let size = (width.min(height) / 6).max(2);
+        let step = (size / 4).max(1) as u64;
+        let square_x = ((self.frame * step) % (width - size + 1) as u64) as u32;
+        let square_y = (height - size) / 3;
+        let circle_x = width * 2 / 3;
+        let circle_y = ((self.frame * step) % (height - size + 1) as u64) as u32 + size / 2;
+        let radius = (size / 2) as i64;
+        RgbImage::from_fn(width, height, |x, y| {
+            let dx = x as i64 - circle_x as i64;
+            let dy = y as i64 - circle_y as i64;
+            if x >= square_x && x < square_x + size && y >= square_y && y < square_y + size {
+                Rgb([220, 40, 40])
+            } else if dx * dx + dy * dy <= radius * radius {
+                Rgb([40, 200, 60])
+            } else {
+                Rgb([16, 16, (32 + y * 64 / height) as u8])
+            }
+        })
+    }
+}
+
+impl CameraLike for PatternCamera {
+    fn initialize(&mut self, config: ConfigMap) -> Result<(), HardwareError> {
+        self.resolution = as_resolution_tuple(
+            &config
+                .get_string("resolution")
+                .unwrap_or_else(|| "640x480".to_string()),
+        )?;
+        if self.resolution.0 < 2 || self.resolution.1 < 2 {
+            return Err(HardwareError::InvalidSettings(
+                "the pattern camera resolution must be at least 2x2".to_string(),
+            ));
+        }
+        self.pattern = match config.get_string("pattern") {
+            Some(p) => Pattern::from_str(&p)?,
+            None => Pattern::Bars,
+        };
+        self.noise = config.get_u32("pattern_noise").unwrap_or(0).min(255) as u8;
+        self.timestamp = config.flag("pattern_timestamp");
+        self.seed = config.get_u32("pattern_seed").unwrap_or(1).max(1) as u64;
+        self.frame = 0;
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), HardwareError> {
+        self.active = None;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Frame, HardwareError> {
+        let image = self.render();
+        self.frame += 1;
+        self.active = Some(image);
+        match self.active.as_ref() {
+            Some(image) => Ok(Encoding::new().frame_from_slice(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+            )?),
+            None => Err(HardwareError::DeviceNoLongerAvailable(
+                "Invalid pattern frame".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PatternCamera;
+    use crate::hardware::CameraLike;
+    use crate::resources::ConfigMap;
+
+    fn camera(pattern: &str, noise: &str) -> PatternCamera {
+        let mut config = ConfigMap::new();
+        config.set("resolution", "320x240");
+        config.set("pattern", pattern);
+        config.set("pattern_noise", noise);
+        config.set("pattern_timestamp", "1");
+        let mut camera = PatternCamera::new();
+        camera.initialize(config).unwrap();
+        camera
+    }
+
+    #[test]
+    pub fn test_generate_patterns() {
+        for pattern in ["bars", "gradient", "shapes"].iter() {
+            let mut camera = camera(pattern, "0");
+            let frame = camera.next().unwrap();
+            assert_eq!(frame.width(), 320);
+            assert_eq!(frame.height(), 240);
+        }
+    }
+
+    #[test]
+    pub fn test_shapes_move_between_frames() {
+        let mut camera = camera("shapes", "0");
+        let first = camera.next().unwrap().as_raw().to_vec();
+        let second = camera.next().unwrap().as_raw().to_vec();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    pub fn test_noise_is_reproducible() {
+        let first = camera("bars", "20").next().unwrap().as_raw()[96000..96100].to_vec();
+        let second = camera("bars", "20").next().unwrap().as_raw()[96000..96100].to_vec();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    pub fn test_invalid_pattern() {
+        let mut config = ConfigMap::new();
+        config.set("pattern", "plaid");
+        assert!(PatternCamera::new().initialize(config).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+#[cfg(feature = "pi")]
+use crate::encoding::Encoding;
+use crate::hardware::as_resolution_tuple;
+use crate::hardware::error::HardwareError;
+#[cfg(feature = "pi")]
+use crate::hardware::{CameraLike, Frame};
+use crate::resources::ConfigMap;
+#[cfg(feature = "pi")]
+use rust_pi_capture::{AwbMode, Capture, CaptureSettings, DrcStrength, ExposureMode, MeteringMode};
+use std::str::FromStr;
+
+/// The settings for the pi camera, as read from the manifest.
+/// This is kept separate from the camera itself so it can be checked on hosts without mmal.
+#[derive(Debug)]
+pub struct PiCameraSettings {
+    pub resolution: (u32, u32),
+
+    /// The sensor ISO; 0 for auto.
+    pub iso: u32,
+
+    /// The shutter speed in microseconds; 0 for auto.
+    pub shutter: u32,
+
+    /// The white balance mode, eg. auto, sunlight, cloudy.
+    pub awb: Option<String>,
+
+    /// Manual red and blue white balance gains, eg. "1.5,1.2"; requires awb = "off".
+    pub awb_gains: Option<(f32, f32)>,
+
+    /// The exposure mode, eg. auto, night, verylong.
+    pub exposure: Option<String>,
+
+    /// The metering mode; average, spot, backlit or matrix.
+    pub metering: Option<String>,
+
+    /// The dynamic range compression; off, low, medium or high.
+    pub drc: Option<String>,
+
+    pub hflip: bool,
+    pub vflip: bool,
+
+    /// Rotation in degrees; a multiple of 90.
+    pub rotation: i32,
+}
+
+impl PiCameraSettings {
+    pub fn from_config(config: &ConfigMap) -> Result<PiCameraSettings, HardwareError> {
+        Ok(PiCameraSettings {
+            resolution: as_resolution_tuple(
+                &config
+                    .get_string("resolution")
+                    .unwrap_or_else(|| "1280x720".to_string()),
+            )?,
+            iso: PiCameraSettings::get_number(config, "iso")?,
+            shutter: PiCameraSettings::get_number(config, "shutter")?,
+            awb: config.get_string("awb"),
+            awb_gains: PiCameraSettings::get_gains(config, "awb_gains")?,
+            exposure: config.get_string("exposure"),
+            metering: config.get_string("metering"),
+            drc: config.get_string("drc"),
+            hflip: config.flag("hflip"),
+            vflip: config.flag("vflip"),
+            rotation: PiCameraSettings::get_rotation(config, "rotation")?,
+        })
+    }
+
+    fn get_rotation(config: &ConfigMap, key: &str) -> Result<i32, HardwareError> {
+        let rotation = PiCameraSettings::get_number::<i32>(config, key)?;
+        if rotation % 90 != 0 {
+            return Err(HardwareError::InvalidSettings(format!(
+                "{} is not a valid value for {}; use a multiple of 90",
+                rotation, key
+            )));
+        }
+        Ok(rotation)
+    }
+
+    fn get_gains(config: &ConfigMap, key: &str) -> Result<Option<(f32, f32)>, HardwareError> {
+        let value = match config.get_string(key) {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let parts: Vec<Result<f32, _>> =
+            value.split(',').map(|v| v.trim().parse::<f32>()).collect();
+        match parts.as_slice() {
+            [Ok(red), Ok(blue)] => Ok(Some((*red, *blue))),
+            _ => Err(HardwareError::InvalidSettings(format!(
+                "{} is not a valid value for {}; use the format RED,BLUE, eg. 1.5,1.2",
+                value, key
+            ))),
+        }
+    }
+
+    #[cfg(feature = "pi")]
+    fn as_capture_settings(&self) -> Result<CaptureSettings, HardwareError> {
+        let mut builder = CaptureSettings::builder(self.resolution)
+            .iso(self.iso)
+            .shutter_speed(self.shutter)
+            .flip(self.hflip, self.vflip)
+            .rotation(self.rotation);
+        if let Some(awb) = &self.awb {
+            builder = builder.awb_mode(awb.parse::<AwbMode>()?);
+        }
+        if let Some((red, blue)) = self.awb_gains {
+            builder = builder.awb_gains(red, blue);
+        }
+        if let Some(exposure) = &self.exposure {
+            builder = builder.exposure_mode(exposure.parse::<ExposureMode>()?);
+        }
+        if let Some(metering) = &self.metering {
+            builder = builder.metering_mode(metering.parse::<MeteringMode>()?);
+        }
+        if let Some(drc) = &self.drc {
+            builder = builder.drc(drc.parse::<DrcStrength>()?);
+        }
+        Ok(builder.build()?)
+    }
+
+    fn get_number<T: FromStr + Default>(config: &ConfigMap, key: &str) -> Result<T, HardwareError> {
+        match config.get_string(key) {
+            Some(value) => str::parse::<T>(&value).map_err(|_| {
+                HardwareError::InvalidSettings(format!(
+                    "{} is not a valid value for {}",
+                    value, key
+                ))
+            }),
+            None => Ok(T::default()),
+        }
+    }
+}
+
+#[cfg(feature = "pi")]
+pub struct PiCamera {
+    buffer: Option<Vec<u8>>,
+    capture: Option<Capture>,
+    encoder: Encoding,
+}
+
+#[cfg(feature = "pi")]
+impl Default for PiCamera {
+    fn default() -> Self {
+        PiCamera {
+            capture: None,
+            buffer: None,
+            encoder: Encoding::new(),
+        }
+    }
+}
+
+#[cfg(feature = "pi")]
+impl PiCamera {
+    pub fn new() -> PiCamera {
+        Default::default()
+    }
+}
+
+#[cfg(feature = "pi")]
+impl CameraLike for PiCamera {
+    fn initialize(&mut self, config: ConfigMap) -> Result<(), HardwareError> {
+        let settings = PiCameraSettings::from_config(&config)?;
+        let mut capture = Capture::new(settings.as_capture_settings()?);
+        let buffer_size = capture.get_buffer_size()?;
+        self.buffer = Some(vec![0u8; buffer_size]);
+
+        capture.init()?;
+        self.capture = Some(capture);
+
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), HardwareError> {
+        if let Some(capture) = self.capture.take() {
+            capture.shutdown();
+        }
+        self.buffer = None;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Frame, HardwareError> {
+        if let Some(capture) = self.capture.as_mut() {
+            if let Some(buffer) = self.buffer.as_mut() {
+                capture.read(buffer.as_mut())?;
+                let (width, height) = capture.settings.resolution();
+                let frame = self
+                    .encoder
+                    .frame_from_slice(buffer.as_slice(), width, height)?;
+                return Ok(frame);
+            }
+        }
+        Err(HardwareError::DeviceNoLongerAvailable(
+            "Device state is invalid; call initialize() first".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PiCameraSettings;
+    use crate::resources::ConfigMap;
+
+    #[test]
+    pub fn test_pi_settings_from_config() {
+        let mut config = ConfigMap::new();
+        config.set("backend", "mmal");
+        config.set("resolution", "1920x1080");
+        config.set("iso", "400");
+        config.set("shutter", "20000");
+        config.set("awb", "off");
+        config.set("awb_gains", "1.5, 1.2");
+        config.set("vflip", "1");
+        config.set("rotation", "180");
+
+        let settings = PiCameraSettings::from_config(&config).unwrap();
+        assert_eq!(settings.resolution, (1920, 1080));
+        assert_eq!(settings.iso, 400);
+        assert_eq!(settings.shutter, 20000);
+        assert_eq!(settings.awb, Some("off".to_string()));
+        assert_eq!(settings.awb_gains, Some((1.5, 1.2)));
+        assert!(!settings.hflip);
+        assert!(settings.vflip);
+        assert_eq!(settings.rotation, 180);
+    }
+
+    #[test]
+    pub fn test_pi_settings_rejects_invalid_values() {
+        let mut config = ConfigMap::new();
+        config.set("iso", "fast");
+        assert!(PiCameraSettings::from_config(&config).is_err());
+
+        let mut config = ConfigMap::new();
+        config.set("awb_gains", "1.5");
+        assert!(PiCameraSettings::from_config(&config).is_err());
+
+        let mut config = ConfigMap::new();
+        config.set("rotation", "45");
+        assert!(PiCameraSettings::from_config(&config).is_err());
+    }
+
+    #[test]
+    pub fn test_pi_settings_negative_rotation() {
+        let mut config = ConfigMap::new();
+        config.set("rotation", "-90");
+        let settings = PiCameraSettings::from_config(&config).unwrap();
+        assert_eq!(settings.rotation, -90);
+    }
+}
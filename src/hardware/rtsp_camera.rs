@@ -0,0 +1,184 @@
+use crate::encoding::Encoding;
+use crate::hardware::error::HardwareError;
+use crate::hardware::{as_resolution_tuple, CameraLike, Frame};
+use crate::resources::ConfigMap;
+use rust_ffmpeg_capture::{Capture, CaptureSettings};
+
+/// Grabs a single frame from a network stream (typically rtsp) using libav.
+/// The stream is opened for each frame and closed again immediately, so that with long
+/// sample intervals we don't hold a connection open or return stale buffered frames.
+pub struct RtspCamera {
+    url: String,
+    transport: String,
+    timeout: u32,
+    resolution: (u32, u32),
+    buffer: Option<Vec<u8>>,
+    encoder: Encoding,
+}
+
+impl Default for RtspCamera {
+    fn default() -> Self {
+        RtspCamera {
+            url: String::new(),
+            transport: "tcp".to_string(),
+            timeout: 10000,
+            resolution: (0, 0),
+            buffer: None,
+            encoder: Encoding::new(),
+        }
+    }
+}
+
+impl RtspCamera {
+    pub fn new() -> RtspCamera {
+        Default::default()
+    }
+
+    fn capture_settings(&self) -> CaptureSettings {
+        let is_rtsp = self.url.starts_with("rtsp://");
+        let options = if is_rtsp {
+            vec![
+                ("rtsp_transport".to_string(), self.transport.clone()),
+                (
+                    "stimeout".to_string(),
+                    format!("{}", u64::from(self.timeout) * 1000),
+                ),
+            ]
+        } else {
+            vec![(
+                "timeout".to_string(),
+                format!("{}", u64::from(self.timeout) * 1000),
+            )]
+        };
+        CaptureSettings {
+            backend: if is_rtsp {
+                "rtsp".to_string()
+            } else {
+                String::new()
+            },
+            device: self.url.clone(),
+            framerate: 1,
+            resolution: self.resolution,
+            pixel_format: String::new(),
+            options,
+        }
+    }
+}
+
+impl CameraLike for RtspCamera {
+    fn initialize(&mut self, config: ConfigMap) -> Result<(), HardwareError> {
+        self.url = match config.get_string("rtsp_url") {
+            Some(u) => u,
+            None => {
+                return Err(HardwareError::InvalidSettings(
+                    "rtsp_url is required for the rtsp camera".to_string(),
+                ));
+            }
+        };
+        self.resolution =
+            as_resolution_tuple(&config.get_string("resolution").unwrap_or_default())?;
+        self.transport = config
+            .get_string("rtsp_transport")
+            .unwrap_or_else(|| "tcp".to_string());
+        self.timeout = config.get_u32("rtsp_timeout").unwrap_or(10000);
+        self.buffer = Some(vec![
+            0u8;
+            (self.resolution.0 * self.resolution.1 * 3) as usize
+        ]);
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<(), HardwareError> {
+        self.buffer = None;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Frame, HardwareError> {
+        let mut capture = Capture::new(self.capture_settings());
+        if let Some(buffer) = self.buffer.as_mut() {
+            let result = capture.init().and_then(|_| capture.read(buffer.as_mut()));
+            capture.shutdown();
+            result?;
+            let frame = self.encoder.frame_from_slice(
+                buffer.as_slice(),
+                self.resolution.0,
+                self.resolution.1,
+            )?;
+            return Ok(frame);
+        }
+        Err(HardwareError::DeviceNoLongerAvailable(
+            "Device state is invalid; call initialize() first".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RtspCamera;
+    use crate::hardware::test_server::{TestAuth, TestServer};
+    use crate::hardware::CameraLike;
+    use crate::resources::ConfigMap;
+    use rust_ffmpeg_capture::has_input_protocol;
+    use std::net::TcpListener;
+
+    #[test]
+    pub fn test_requires_resolution() {
+        let mut config = ConfigMap::new();
+        config.set("rtsp_url", "rtsp://127.0.0.1:8554/stream");
+        assert!(RtspCamera::new().initialize(config).is_err());
+    }
+
+    #[test]
+    pub fn test_timeout_in_microseconds() {
+        let mut config = ConfigMap::new();
+        config.set("rtsp_url", "rtsp://127.0.0.1:8554/stream");
+        config.set("resolution", "64x64");
+        config.set("rtsp_timeout", "5000000");
+        let mut camera = RtspCamera::new();
+        camera.initialize(config).unwrap();
+        let settings = camera.capture_settings();
+        assert_eq!(settings.backend, "rtsp");
+        assert!(settings
+            .options
+            .contains(&("stimeout".to_string(), "5000000000".to_string())));
+    }
+
+    /// Nothing listens on the port, so grabbing a frame must fail rather than wait forever
+    #[test]
+    pub fn test_unreachable_stream_fails() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ConfigMap::new();
+        config.set("rtsp_url", format!("rtsp://127.0.0.1:{}/stream", port));
+        config.set("resolution", "64x64");
+        config.set("rtsp_timeout", "1000");
+
+        let mut camera = RtspCamera::new();
+        camera.initialize(config).unwrap();
+        assert!(camera.next().is_err());
+        camera.shutdown().unwrap();
+    }
+
+    /// libav opens http urls as well, so the stand in server can serve a single frame stream.
+    /// Skipped when libav was built without the http protocol.
+    #[test]
+    pub fn test_grab_single_frame() {
+        if !has_input_protocol("http") {
+            eprintln!("skipping test_grab_single_frame: libav has no http protocol");
+            return;
+        }
+        let server = TestServer::start(TestAuth::None);
+        let mut config = ConfigMap::new();
+        config.set("rtsp_url", server.url("/stream.png"));
+        config.set("resolution", "256x256");
+
+        let mut camera = RtspCamera::new();
+        camera.initialize(config).unwrap();
+        let frame = camera.next().unwrap();
+        assert_eq!(frame.width(), 256);
+        camera.shutdown().unwrap();
+    }
+}
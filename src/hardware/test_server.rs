@@ -0,0 +1,102 @@
+//! A local http server standing in for a network camera in tests.
+use crate::hardware::http_client::parse_challenge;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+const FRAME: &str = "test/data/frames/frame_00000000.png";
+const REALM: &str = "camera";
+const NONCE: &str = "dcd98b7102dd2f0e8b11d0f600bfb0c093";
+
+#[derive(Clone, Copy)]
+pub enum TestAuth {
+    None,
+    Basic(&'static str, &'static str),
+    Digest(&'static str, &'static str),
+}
+
+pub struct TestServer {
+    port: u16,
+}
+
+impl TestServer {
+    /// Serve the first test frame on every path until the test process exits.
+    pub fn start(auth: TestAuth) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                let _ = TestServer::respond(stream, auth);
+            }
+        });
+        TestServer { port }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    fn respond(mut stream: TcpStream, auth: TestAuth) -> std::io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut authorization = None;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if line.to_lowercase().starts_with("authorization:") {
+                authorization = Some(line["authorization:".len()..].trim().to_string());
+            }
+        }
+
+        if !TestServer::is_authorized(auth, authorization) {
+            let challenge = match auth {
+                TestAuth::Digest(_, _) => format!(
+                    "Digest realm=\"{}\", qop=\"auth\", nonce=\"{}\", opaque=\"5ccc069c\"",
+                    REALM, NONCE
+                ),
+                _ => format!("Basic realm=\"{}\"", REALM),
+            };
+            return write!(
+                stream,
+                "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: {}\r\nContent-Length: 0\r\n\r\n",
+                challenge
+            );
+        }
+
+        let body = fs::read(FRAME)?;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )?;
+        stream.write_all(&body)
+    }
+
+    fn is_authorized(auth: TestAuth, authorization: Option<String>) -> bool {
+        match (auth, authorization) {
+            (TestAuth::None, _) => true,
+            (TestAuth::Basic(user, pass), Some(value)) => {
+                value == format!("Basic {}", base64::encode(format!("{}:{}", user, pass)))
+            }
+            (TestAuth::Digest(user, pass), Some(value)) if value.starts_with("Digest") => {
+                let params = parse_challenge(&value["Digest".len()..]);
+                let get = |key: &str| params.get(key).cloned().unwrap_or_default();
+                let ha1 = md5::compute(format!("{}:{}:{}", user, REALM, pass));
+                let ha2 = md5::compute(format!("GET:{}", get("uri")));
+                let expected = md5::compute(format!(
+                    "{:x}:{}:{}:{}:{}:{:x}",
+                    ha1,
+                    NONCE,
+                    get("nc"),
+                    get("cnonce"),
+                    get("qop"),
+                    ha2
+                ));
+                get("username") == user && get("response") == format!("{:x}", expected)
+            }
+            _ => false,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod analysis;
 pub mod app;
 pub mod encoding;
 pub mod hardware;
@@ -1,10 +1,27 @@
+mod atomic_file;
+mod capture_control;
 mod config_map;
+mod disk_space;
+mod folder_layout;
 mod lock_file;
 mod resource_folder;
+mod retention;
+mod shutdown_signal;
 mod time_probe;
 
+pub use self::atomic_file::{is_temp_path, temp_path, write_atomic};
+pub use self::capture_control::{CaptureControl, ControlCommand};
 pub use self::config_map::ConfigMap;
-pub use self::lock_file::{LockError, LockFile};
+pub use self::disk_space::available_space;
+pub use self::folder_layout::FolderLayout;
+pub use self::lock_file::{LockError, LockFile, LockOwner};
+pub use self::resource_folder::FileWalk;
 pub use self::resource_folder::ResourceError;
 pub use self::resource_folder::ResourceFolder;
+pub use self::retention::{
+    LowSpaceAction, Retention, RetentionError, RetentionPolicy, RetentionReport,
+};
+pub use self::shutdown_signal::{install_shutdown_handler, shutdown_requested};
+#[cfg(test)]
+pub(crate) use self::shutdown_signal::{lock_signals_for_test, set_shutdown_requested};
 pub use self::time_probe::{TimeProbe, TimeProbeConfig, TimeProbeError, TimeSnapshot};
@@ -0,0 +1,89 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const TEMP_EXTENSION: &str = "partial";
+
+/// The file which `write_atomic` writes to before moving it to path; a hidden file in the
+/// same folder, so the rename never crosses filesystems.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.{}", name, TEMP_EXTENSION))
+}
+
+/// True for files left behind by a `write_atomic` which never finished
+pub fn is_temp_path(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    name.starts_with('.') && path.extension().map(|e| e == TEMP_EXTENSION) == Some(true)
+}
+
+/// Write a file so it either has the complete new content or does not exist, even if
+/// the power is lost part way: the content goes to a temp file, which is synced to disk
+/// and then renamed into place.
+pub fn write_atomic<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let temp = temp_path(path);
+    let result = (|| {
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        write(&mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+    sync_parent(path);
+    Ok(())
+}
+
+/// Make the rename itself durable; not every platform can open a folder, so this is best effort
+fn sync_parent(path: &Path) {
+    if let Some(parent) = path.parent() {
+        let parent = if parent.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            parent
+        };
+        if let Ok(folder) = File::open(parent) {
+            let _ = folder.sync_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_temp_path, temp_path, write_atomic};
+    use std::fs;
+    use std::io::{self, Write};
+    use std::path::Path;
+
+    #[test]
+    pub fn test_write_atomic() {
+        fs::create_dir_all("test/output").unwrap();
+        let path = Path::new("test/output/atomic.txt");
+        assert!(is_temp_path(&temp_path(path)));
+        assert!(!is_temp_path(path));
+
+        write_atomic(path, |w| w.write_all(b"complete")).unwrap();
+        assert_eq!(fs::read(path).unwrap(), b"complete");
+
+        let failed = write_atomic(path, |w| {
+            w.write_all(b"half")?;
+            Err(io::Error::other("power lost"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read(path).unwrap(), b"complete");
+        assert!(!temp_path(path).exists());
+        fs::remove_file(path).unwrap();
+    }
+}
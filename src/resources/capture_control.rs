@@ -0,0 +1,136 @@
+use crate::resources::shutdown_signal::shutdown_requested;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// A request to change what the capture loop is doing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ControlCommand {
+    Pause,
+    Resume,
+    Stop,
+    CaptureNow,
+
+    /// Change the time between samples, in ms
+    SetInterval(u64),
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    /// Parse a command name; set-interval takes the interval after a colon, eg. interval:5000
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match s.as_str() {
+            "pause" => return Ok(ControlCommand::Pause),
+            "resume" => return Ok(ControlCommand::Resume),
+            "stop" => return Ok(ControlCommand::Stop),
+            "capture" | "capture-now" => return Ok(ControlCommand::CaptureNow),
+            _ => {}
+        }
+        if let Some(value) = s.strip_prefix("interval:") {
+            if let Ok(interval) = str::parse::<u64>(value) {
+                if interval > 0 {
+                    return Ok(ControlCommand::SetInterval(interval));
+                }
+            }
+        }
+        Err(format!(
+            "{} is not a valid command; use pause, resume, stop, capture or interval:MS",
+            s
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+struct ControlState {
+    paused: bool,
+    stopped: bool,
+    capture_now: bool,
+    interval: Option<u64>,
+    signals: bool,
+}
+
+/// Lets another thread (eg. the http server) pause, resume, stop or trigger the `TimeProbe`
+/// while it is running. Clones share the same state.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureControl {
+    state: Arc<Mutex<ControlState>>,
+}
+
+impl CaptureControl {
+    pub fn new() -> CaptureControl {
+        Default::default()
+    }
+
+    /// Also stop once SIGINT or SIGTERM is received; see `install_shutdown_handler`
+    pub fn with_signals(self) -> CaptureControl {
+        if let Ok(mut state) = self.state.lock() {
+            state.signals = true;
+        }
+        self
+    }
+
+    pub fn apply(&self, command: ControlCommand) {
+        if let Ok(mut state) = self.state.lock() {
+            match command {
+                ControlCommand::Pause => state.paused = true,
+                ControlCommand::Resume => state.paused = false,
+                ControlCommand::Stop => state.stopped = true,
+                ControlCommand::CaptureNow => state.capture_now = true,
+                ControlCommand::SetInterval(interval) => state.interval = Some(interval),
+            }
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().map(|s| s.paused).unwrap_or(false)
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state
+            .lock()
+            .map(|s| s.stopped || (s.signals && shutdown_requested()))
+            .unwrap_or(false)
+    }
+
+    /// The interval set at runtime, if any
+    pub fn interval(&self) -> Option<u64> {
+        self.state.lock().ok().and_then(|s| s.interval)
+    }
+
+    /// True once for each capture-now request
+    pub fn take_capture_now(&self) -> bool {
+        match self.state.lock() {
+            Ok(mut state) => std::mem::replace(&mut state.capture_now, false),
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaptureControl, ControlCommand};
+    use std::str::FromStr;
+
+    #[test]
+    pub fn test_commands() {
+        assert_eq!(
+            ControlCommand::from_str("interval:2500").unwrap(),
+            ControlCommand::SetInterval(2500)
+        );
+        assert!(ControlCommand::from_str("interval:0").is_err());
+        assert!(ControlCommand::from_str("explode").is_err());
+
+        let control = CaptureControl::new();
+        let shared = control.clone();
+        shared.apply(ControlCommand::Pause);
+        shared.apply(ControlCommand::CaptureNow);
+        assert!(control.is_paused());
+        assert!(control.take_capture_now());
+        assert!(!control.take_capture_now());
+        shared.apply(ControlCommand::Resume);
+        shared.apply(ControlCommand::Stop);
+        assert!(!control.is_paused());
+        assert!(control.is_stopped());
+    }
+}
@@ -50,6 +50,9 @@ impl ConfigMap {
     }
 
     pub fn get_u32<T: AsRef<str>>(&self, key: T) -> Option<u32> {
+        if !self.data.contains_key(key.as_ref()) {
+            return None;
+        }
         match str::parse::<u32>(&self.data[key.as_ref()]) {
             Ok(v) => Some(v),
             Err(_) => None,
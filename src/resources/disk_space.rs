@@ -0,0 +1,34 @@
+use std::path::Path;
+
+/// The space available to this user on the filesystem holding path, in bytes, if the
+/// platform can report it.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+        return None;
+    }
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::available_space;
+    use std::path::Path;
+
+    #[test]
+    #[cfg(unix)]
+    pub fn test_available_space() {
+        assert!(available_space(Path::new(".")).unwrap() > 0);
+        assert!(available_space(Path::new("/no/such/folder")).is_none());
+    }
+}
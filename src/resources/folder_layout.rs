@@ -0,0 +1,132 @@
+use crate::resources::ResourceError;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How frames are arranged under the output folder, eg. `YYYY/MM/DD/HH` to save each frame
+/// in a folder for the hour (in UTC) it was captured, or an empty layout for a flat folder.
+///
+/// Folder names only ever contain digits, '-' and '_', which is how `ResourceFolder::walk`
+/// tells them apart from other folders, such as 'rejected', in the output folder. Layouts
+/// go from years down to hours, so sorting folders by name sorts them by capture time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FolderLayout {
+    /// A chrono format string for each level of folders
+    levels: Vec<String>,
+}
+
+impl FolderLayout {
+    pub fn flat() -> FolderLayout {
+        Default::default()
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /// The folder, relative to the output folder, for a frame captured at time
+    pub fn subfolder(&self, time: &DateTime<Utc>) -> PathBuf {
+        self.levels
+            .iter()
+            .map(|level| time.format(level).to_string())
+            .collect()
+    }
+
+    /// True if name could be a folder created by a layout
+    pub fn is_layout_folder(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '-' || c == '_')
+    }
+
+    /// True if every folder in path, relative to the output folder, could be a layout folder
+    pub fn is_layout_path(path: &Path) -> bool {
+        path.parent()
+            .map(|parent| {
+                parent
+                    .components()
+                    .all(|c| FolderLayout::is_layout_folder(&c.as_os_str().to_string_lossy()))
+            })
+            .unwrap_or(true)
+    }
+}
+
+impl FromStr for FolderLayout {
+    type Err = ResourceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() || s == "flat" {
+            return Ok(FolderLayout::flat());
+        }
+        let mut levels = Vec::new();
+        let mut last_unit = None;
+        for level in s.split('/') {
+            let mut format = String::new();
+            let mut rest = level;
+            while !rest.is_empty() {
+                let (token, size, unit) = if rest.starts_with("YYYY") {
+                    ("%Y", 4, Some(0))
+                } else if rest.starts_with("MM") {
+                    ("%m", 2, Some(1))
+                } else if rest.starts_with("DD") {
+                    ("%d", 2, Some(2))
+                } else if rest.starts_with("HH") {
+                    ("%H", 2, Some(3))
+                } else if rest.starts_with('-') || rest.starts_with('_') {
+                    (&rest[..1], 1, None)
+                } else {
+                    return Err(ResourceError::InvalidLayout(format!(
+                        "{} is not a valid folder layout; use YYYY, MM, DD and HH separated by /, - or _",
+                        s
+                    )));
+                };
+                if unit.is_some() {
+                    if unit <= last_unit {
+                        return Err(ResourceError::InvalidLayout(format!(
+                            "{} is not a valid folder layout; go from YYYY down to HH so folders sort in capture order",
+                            s
+                        )));
+                    }
+                    last_unit = unit;
+                }
+                format.push_str(token);
+                rest = &rest[size..];
+            }
+            if format.is_empty() {
+                return Err(ResourceError::InvalidLayout(format!(
+                    "{} has an empty folder level",
+                    s
+                )));
+            }
+            levels.push(format);
+        }
+        Ok(FolderLayout { levels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FolderLayout;
+    use crate::resources::TimeSnapshot;
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    #[test]
+    pub fn test_folder_layout() {
+        let time = TimeSnapshot::from_timestamp(1583065800000).utc;
+        let layout = FolderLayout::from_str("YYYY/MM/DD/HH").unwrap();
+        assert_eq!(layout.subfolder(&time), PathBuf::from("2020/03/01/12"));
+        let layout = FolderLayout::from_str("YYYY-MM-DD/HH").unwrap();
+        assert_eq!(layout.subfolder(&time), PathBuf::from("2020-03-01/12"));
+        assert!(FolderLayout::from_str("flat").unwrap().is_flat());
+        assert!(FolderLayout::from_str("YYYY/week").is_err());
+        assert!(FolderLayout::from_str("YYYY//DD").is_err());
+        assert!(FolderLayout::from_str("DD/MM/YYYY").is_err());
+        assert!(FolderLayout::from_str("YYYY/MM/MM").is_err());
+
+        assert!(FolderLayout::is_layout_path(Path::new("2020/03/1-a.png")));
+        assert!(!FolderLayout::is_layout_path(Path::new("rejected/1-a.png")));
+    }
+}
@@ -1,39 +1,202 @@
 pub use self::error::LockError;
-use std::fs;
+use chrono::{DateTime, Utc};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
 
 /// Tracks an external file to check if a lock is true or not.
 /// When the file is removed for any reason, the lock is released.
+///
+/// While locked, the file holds an exclusive advisory lock and records the pid and start
+/// time of the owner, so a second process using the same file fails to lock it. A file
+/// left behind by a process which has exited is stale and is taken over.
 pub struct LockFile {
     path: PathBuf,
+    file: Option<File>,
+    stale: Option<LockOwner>,
+}
+
+/// The process recorded in a lock file
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub started: Option<DateTime<Utc>>,
+}
+
+impl LockOwner {
+    fn current() -> LockOwner {
+        LockOwner {
+            pid: std::process::id(),
+            started: Some(Utc::now()),
+        }
+    }
+
+    /// Parse the `pid\nstarted` format written by `lock`; older files just contain "LOCK"
+    fn parse(content: &str) -> Option<LockOwner> {
+        let mut lines = content.lines();
+        let pid = lines
+            .next()
+            .and_then(|l| str::parse::<u32>(l.trim()).ok())?;
+        let started = lines
+            .next()
+            .and_then(|l| DateTime::parse_from_rfc3339(l.trim()).ok())
+            .map(|t| t.with_timezone(&Utc));
+        Some(LockOwner { pid, started })
+    }
+
+    fn is_alive(&self) -> bool {
+        process_alive(self.pid)
+    }
 }
 
 impl LockFile {
     pub fn new<T: AsRef<Path>>(path: T) -> LockFile {
         LockFile {
             path: PathBuf::from(path.as_ref()),
+            file: None,
+            stale: None,
         }
     }
 
-    pub fn lock(&self) -> Result<(), LockError> {
-        if !self.path.exists() {
-            fs::write(&self.path, b"LOCK")?;
+    /// Take the lock, or fail with `LockError::AlreadyLocked` if another live process has it
+    pub fn lock(&mut self) -> Result<(), LockError> {
+        if self.file.is_some() {
+            return Ok(());
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        let mut content = String::new();
+        let _ = file.read_to_string(&mut content);
+        let previous = LockOwner::parse(&content);
+
+        match try_lock_exclusive(&file) {
+            LockAttempt::Acquired => {}
+            LockAttempt::Held => {
+                let owner = previous.unwrap_or(LockOwner {
+                    pid: 0,
+                    started: None,
+                });
+                return Err(LockError::AlreadyLocked {
+                    pid: owner.pid,
+                    started: owner.started.map(|t| t.to_rfc3339()),
+                });
+            }
+            LockAttempt::Unsupported => {
+                // No advisory locks on this filesystem; fall back to checking the pid
+                if let Some(owner) = previous.as_ref() {
+                    if owner.pid != std::process::id() && owner.is_alive() {
+                        return Err(LockError::AlreadyLocked {
+                            pid: owner.pid,
+                            started: owner.started.map(|t| t.to_rfc3339()),
+                        });
+                    }
+                }
+            }
         }
+
+        let owner = LockOwner::current();
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(
+            file,
+            "{}\n{}\n",
+            owner.pid,
+            owner.started.map(|t| t.to_rfc3339()).unwrap_or_default()
+        )?;
+        file.sync_all()?;
+        self.stale = previous.filter(|p| p.pid != owner.pid);
+        self.file = Some(file);
         Ok(())
     }
 
+    /// The owner of a stale lock which was taken over by the last call to `lock`, if any
+    pub fn stale_owner(&self) -> Option<&LockOwner> {
+        self.stale.as_ref()
+    }
+
+    /// The process recorded in the lock file, whether or not it is still running
+    pub fn owner(&self) -> Option<LockOwner> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| LockOwner::parse(&content))
+    }
+
     pub fn is_locked(&self) -> bool {
         self.path.exists()
     }
 
-    pub fn unlock(&self) -> Result<(), LockError> {
-        if self.path.exists() {
-            fs::remove_file(&self.path)?;
+    /// Release the lock and remove the file, unless it now belongs to another process
+    pub fn unlock(&mut self) -> Result<(), LockError> {
+        if self.file.is_none() {
+            return Ok(());
         }
+        // Remove the file while still holding the lock, so another process can't take it
+        // over between the owner check and the removal
+        let ours = self
+            .owner()
+            .map(|owner| owner.pid == std::process::id())
+            .unwrap_or(false);
+        let removed = if ours && self.path.exists() {
+            fs::remove_file(&self.path)
+        } else {
+            Ok(())
+        };
+        self.file = None;
+        removed?;
         Ok(())
     }
 }
 
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = self.unlock();
+    }
+}
+
+enum LockAttempt {
+    Acquired,
+    Held,
+    Unsupported,
+}
+
+#[cfg(unix)]
+fn try_lock_exclusive(file: &File) -> LockAttempt {
+    use std::os::unix::io::AsRawFd;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return LockAttempt::Acquired;
+    }
+    match std::io::Error::last_os_error().raw_os_error() {
+        Some(libc::EWOULDBLOCK) => LockAttempt::Held,
+        _ => LockAttempt::Unsupported,
+    }
+}
+
+#[cfg(not(unix))]
+fn try_lock_exclusive(_file: &File) -> LockAttempt {
+    LockAttempt::Unsupported
+}
+
+#[cfg(unix)]
+fn process_alive(pid: u32) -> bool {
+    if pid == 0 || pid > i32::MAX as u32 {
+        return false;
+    }
+    if unsafe { libc::kill(pid as libc::pid_t, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_alive(_pid: u32) -> bool {
+    false
+}
+
 pub mod error {
     use std::error::Error;
     use std::fmt;
@@ -43,6 +206,12 @@ pub mod error {
     #[derive(Debug)]
     pub enum LockError {
         IoFailed(String),
+
+        /// Another running process holds the lock
+        AlreadyLocked {
+            pid: u32,
+            started: Option<String>,
+        },
     }
 
     impl Display for LockError {
@@ -59,3 +228,49 @@ pub mod error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LockError, LockFile};
+    use std::fs;
+
+    #[test]
+    #[cfg(unix)]
+    pub fn test_exclusive_lock() {
+        fs::create_dir_all("test/output").unwrap();
+        let path = "test/output/exclusive.lock";
+        let _ = fs::remove_file(path);
+
+        let mut first = LockFile::new(path);
+        first.lock().unwrap();
+        assert_eq!(first.owner().unwrap().pid, std::process::id());
+
+        let mut second = LockFile::new(path);
+        match second.lock() {
+            Err(LockError::AlreadyLocked { pid, started }) => {
+                assert_eq!(pid, std::process::id());
+                assert!(started.is_some());
+            }
+            other => panic!("expected AlreadyLocked, got {:?}", other),
+        }
+
+        first.unlock().unwrap();
+        assert!(!first.is_locked());
+        second.lock().unwrap();
+        drop(second);
+        assert!(!std::path::Path::new(path).exists());
+    }
+
+    #[test]
+    pub fn test_takes_over_stale_lock() {
+        fs::create_dir_all("test/output").unwrap();
+        let path = "test/output/stale.lock";
+        fs::write(path, "4000000000\n2021-03-01T12:00:00+00:00\n").unwrap();
+
+        let mut lock = LockFile::new(path);
+        lock.lock().unwrap();
+        assert_eq!(lock.stale_owner().unwrap().pid, 4000000000);
+        assert_eq!(lock.owner().unwrap().pid, std::process::id());
+        lock.unlock().unwrap();
+    }
+}
@@ -1,7 +1,8 @@
 pub use self::error::ResourceError;
+use crate::resources::FolderLayout;
 use std::fs;
 use std::fs::DirEntry;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone)]
 pub struct ResourceFolder {
@@ -66,6 +67,91 @@ impl ResourceFolder {
         sorted.sort_by_key(|v| v.file_name());
         Ok(sorted)
     }
+
+    /// Every file in the folder and in its `FolderLayout` subfolders, in capture order;
+    /// see `walk`. Other subfolders are not included.
+    pub fn walk_files(&self) -> Result<Vec<PathBuf>, ResourceError> {
+        self.walk()?.collect()
+    }
+
+    /// Walk the files in the folder and its `FolderLayout` subfolders, oldest first. Each
+    /// folder is read and sorted by name only when the walk reaches it, and as layouts go
+    /// from years down to hours, file name order in each folder is capture order overall.
+    pub fn walk(&self) -> Result<FileWalk, ResourceError> {
+        FileWalk::new(&self.basepath()?, false)
+    }
+
+    /// Walk the files newest first, so the latest frames can be read without the rest
+    pub fn walk_newest(&self) -> Result<FileWalk, ResourceError> {
+        FileWalk::new(&self.basepath()?, true)
+    }
+}
+
+/// A lazy walk over the files in a folder and its `FolderLayout` subfolders; see
+/// `ResourceFolder::walk`.
+pub struct FileWalk {
+    /// The entries left to visit in each folder being walked, next entry last
+    pending: Vec<Vec<PathBuf>>,
+    newest_first: bool,
+}
+
+impl FileWalk {
+    fn new(folder: &Path, newest_first: bool) -> Result<FileWalk, ResourceError> {
+        let mut walk = FileWalk {
+            pending: Vec::new(),
+            newest_first,
+        };
+        walk.open(folder)?;
+        Ok(walk)
+    }
+
+    fn open(&mut self, folder: &Path) -> Result<(), ResourceError> {
+        let mut entries: Vec<PathBuf> = fs::read_dir(folder)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .collect();
+        entries.sort_by(|a, b| sort_name(a).cmp(&sort_name(b)).then(a.cmp(b)));
+        if !self.newest_first {
+            entries.reverse();
+        }
+        self.pending.push(entries);
+        Ok(())
+    }
+}
+
+impl Iterator for FileWalk {
+    type Item = Result<PathBuf, ResourceError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let path = match self.pending.last_mut()?.pop() {
+                Some(path) => path,
+                None => {
+                    self.pending.pop();
+                    continue;
+                }
+            };
+            if !path.is_dir() {
+                return Some(Ok(path));
+            }
+            let is_layout = path
+                .file_name()
+                .map(|name| FolderLayout::is_layout_folder(&name.to_string_lossy()))
+                .unwrap_or(false);
+            if is_layout {
+                if let Err(err) = self.open(&path) {
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+/// The name a file is sorted by; unfinished temp files sort next to the file they replace
+fn sort_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().trim_start_matches('.').to_string())
+        .unwrap_or_default()
 }
 
 pub mod error {
@@ -76,6 +162,7 @@ pub mod error {
         NotReady,
         NoSuchFolder(String),
         UnableToCreateFolder(String),
+        InvalidLayout(String),
     }
 
     impl std::fmt::Display for ResourceError {
@@ -101,4 +188,66 @@ mod test {
         rf.require()?;
         Ok(())
     }
+
+    #[test]
+    pub fn test_walk_files() -> Result<(), ResourceError> {
+        let base = "test/output/walk";
+        let _ = std::fs::remove_dir_all(base);
+        for (folder, name) in [
+            ("2021/03/02", "300-c.png"),
+            ("2021/03/01", "200-b.png"),
+            ("", "100-a.png"),
+            ("rejected", "150-x.png"),
+        ]
+        .iter()
+        {
+            let folder = std::path::Path::new(base).join(folder);
+            std::fs::create_dir_all(&folder)?;
+            std::fs::write(folder.join(name), b"")?;
+        }
+
+        let rf = ResourceFolder::new(base).require_existing()?;
+        let names: Vec<String> = rf
+            .walk_files()?
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["100-a.png", "200-b.png", "300-c.png"]);
+        std::fs::remove_dir_all(base)?;
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_walk_newest_first() -> Result<(), ResourceError> {
+        let base = "test/output/walk_newest";
+        let _ = std::fs::remove_dir_all(base);
+        for (folder, name) in [
+            ("2021/03/02", "300-c.png"),
+            ("2021/03/02", ".400-d.png.partial"),
+            ("2021/03/01", "200-b.png"),
+            ("", "100-a.png"),
+        ]
+        .iter()
+        {
+            let folder = std::path::Path::new(base).join(folder);
+            std::fs::create_dir_all(&folder)?;
+            std::fs::write(folder.join(name), b"")?;
+        }
+
+        let rf = ResourceFolder::new(base).require_existing()?;
+        let names: Vec<String> = rf
+            .walk_newest()?
+            .take(3)
+            .map(|p| {
+                p.unwrap()
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, vec![".400-d.png.partial", "300-c.png", "200-b.png"]);
+        std::fs::remove_dir_all(base)?;
+        Ok(())
+    }
 }
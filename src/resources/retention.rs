@@ -0,0 +1,458 @@
+pub use self::error::RetentionError;
+use crate::resources::{available_space, FileWalk, ResourceFolder, TimeSnapshot};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const MS_PER_DAY: u128 = 24 * 60 * 60 * 1000;
+
+/// What to do when free space drops below the minimum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LowSpaceAction {
+    /// Stop capturing with `RetentionError::OutOfSpace`
+    Stop,
+
+    /// Delete the oldest frames until there is enough space
+    DeleteOldest,
+}
+
+impl FromStr for LowSpaceAction {
+    type Err = RetentionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stop" => Ok(LowSpaceAction::Stop),
+            "delete-oldest" => Ok(LowSpaceAction::DeleteOldest),
+            _ => Err(RetentionError::InvalidPolicy(format!(
+                "{} is not a valid low space action; use stop or delete-oldest",
+                s
+            ))),
+        }
+    }
+}
+
+/// Limits on the frames kept in an output folder; a limit of 0 is disabled
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    /// Delete the oldest frames once the frames take up more than this many bytes
+    pub max_total_bytes: u64,
+
+    /// Delete frames older than this many days
+    pub max_age_days: u64,
+
+    /// Thin frames older than this many days down to one per thin_interval
+    pub thin_after_days: u64,
+
+    /// The time between the frames kept when thinning, in ms
+    pub thin_interval: u64,
+
+    /// The free space to keep on the filesystem, in bytes
+    pub min_free_bytes: u64,
+
+    pub low_space: LowSpaceAction,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            max_total_bytes: 0,
+            max_age_days: 0,
+            thin_after_days: 0,
+            thin_interval: 60 * 60 * 1000,
+            min_free_bytes: 0,
+            low_space: LowSpaceAction::Stop,
+        }
+    }
+}
+
+/// What a call to `Retention::apply` did
+#[derive(Debug, Default)]
+pub struct RetentionReport {
+    pub removed: Vec<PathBuf>,
+    pub freed: u64,
+
+    /// The size of the frames kept, if every frame was read
+    pub total: Option<u64>,
+    pub free: Option<u64>,
+}
+
+struct StoredFrame {
+    path: PathBuf,
+    timestamp: u128,
+    size: u64,
+}
+
+/// The frames in a folder, oldest first, read from the walk only as they are needed
+struct StoredFrames {
+    walk: FileWalk,
+    loaded: VecDeque<StoredFrame>,
+    finished: bool,
+}
+
+impl StoredFrames {
+    fn new(folder: &ResourceFolder) -> Result<StoredFrames, RetentionError> {
+        Ok(StoredFrames {
+            walk: folder.walk()?,
+            loaded: VecDeque::new(),
+            finished: false,
+        })
+    }
+
+    /// Read the next frame from the walk; false once there are no more
+    fn load_next(&mut self) -> Result<bool, RetentionError> {
+        while !self.finished {
+            let path = match self.walk.next() {
+                Some(path) => path?,
+                None => {
+                    self.finished = true;
+                    break;
+                }
+            };
+            if path.extension().map(|e| e == "png") != Some(true) {
+                continue;
+            }
+            let time = match TimeSnapshot::from_frame_path(&path) {
+                Some(time) => time,
+                None => continue,
+            };
+            let size = match fs::metadata(&path) {
+                Ok(metadata) => metadata.len(),
+                Err(_) => continue,
+            };
+            self.loaded.push_back(StoredFrame {
+                path,
+                timestamp: time.timestamp,
+                size,
+            });
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Read every frame taken before timestamp, or every frame if there is no timestamp
+    fn load_until(&mut self, timestamp: Option<u128>) -> Result<(), RetentionError> {
+        loop {
+            if let (Some(timestamp), Some(last)) = (timestamp, self.loaded.back()) {
+                if last.timestamp >= timestamp {
+                    return Ok(());
+                }
+            }
+            if !self.load_next()? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn pop_oldest(&mut self) -> Result<Option<StoredFrame>, RetentionError> {
+        if self.loaded.is_empty() {
+            self.load_next()?;
+        }
+        Ok(self.loaded.pop_front())
+    }
+}
+
+/// Applies a `RetentionPolicy` to the frames in a folder; frames are the `{timestamp}-*.png`
+/// files saved by the capture, and any other files are left alone. Only the folder and its
+/// `FolderLayout` subfolders are walked, so rejected and quarantined frames are not included.
+pub struct Retention {
+    policy: RetentionPolicy,
+}
+
+impl Retention {
+    pub fn new(policy: RetentionPolicy) -> Retention {
+        Retention { policy }
+    }
+
+    /// Remove frames as the policy requires, given the current time in ms since the epoch.
+    /// Frames are read oldest first and only as far as the limits need; every frame is only
+    /// read when max_total_bytes is set.
+    pub fn apply(
+        &self,
+        folder: &ResourceFolder,
+        now: u128,
+    ) -> Result<RetentionReport, RetentionError> {
+        let mut frames = StoredFrames::new(folder)?;
+        let mut report = RetentionReport::default();
+        let policy = &self.policy;
+        if policy.max_total_bytes > 0 {
+            frames.load_until(None)?;
+        }
+
+        if policy.max_age_days > 0 {
+            let cutoff = now.saturating_sub(policy.max_age_days as u128 * MS_PER_DAY);
+            frames.load_until(Some(cutoff))?;
+            let old = frames
+                .loaded
+                .iter()
+                .take_while(|f| f.timestamp < cutoff)
+                .count();
+            Retention::remove(frames.loaded.drain(..old).collect(), &mut report)?;
+        }
+
+        if policy.thin_after_days > 0 && policy.thin_interval > 0 {
+            let cutoff = now.saturating_sub(policy.thin_after_days as u128 * MS_PER_DAY);
+            frames.load_until(Some(cutoff))?;
+            let mut last_kept: Option<u128> = None;
+            let mut thinned = Vec::new();
+            let mut kept = VecDeque::new();
+            for frame in frames.loaded.drain(..) {
+                let keep = frame.timestamp >= cutoff
+                    || last_kept
+                        .map(|t| frame.timestamp - t >= policy.thin_interval as u128)
+                        .unwrap_or(true);
+                if keep {
+                    if frame.timestamp < cutoff {
+                        last_kept = Some(frame.timestamp);
+                    }
+                    kept.push_back(frame);
+                } else {
+                    thinned.push(frame);
+                }
+            }
+            Retention::remove(thinned, &mut report)?;
+            frames.loaded = kept;
+        }
+
+        if policy.max_total_bytes > 0 {
+            let mut total: u64 = frames.loaded.iter().map(|f| f.size).sum();
+            let mut excess = 0;
+            while total > policy.max_total_bytes && excess < frames.loaded.len() {
+                total -= frames.loaded[excess].size;
+                excess += 1;
+            }
+            Retention::remove(frames.loaded.drain(..excess).collect(), &mut report)?;
+        }
+
+        Retention::remove_empty_folders(&folder.basepath()?, &report.removed);
+        report.free = available_space(&folder.basepath()?);
+        if policy.min_free_bytes > 0 {
+            if let Some(mut free) = report.free {
+                if free < policy.min_free_bytes && policy.low_space == LowSpaceAction::DeleteOldest
+                {
+                    let mut oldest = Vec::new();
+                    while free < policy.min_free_bytes {
+                        match frames.pop_oldest()? {
+                            Some(frame) => {
+                                free += frame.size;
+                                oldest.push(frame);
+                            }
+                            None => break,
+                        }
+                    }
+                    Retention::remove(oldest, &mut report)?;
+                    report.free = available_space(&folder.basepath()?).or(Some(free));
+                    free = report.free.unwrap_or(free);
+                }
+                if free < policy.min_free_bytes {
+                    return Err(RetentionError::OutOfSpace {
+                        free,
+                        required: policy.min_free_bytes,
+                    });
+                }
+            }
+        }
+
+        if frames.finished {
+            report.total = Some(frames.loaded.iter().map(|f| f.size).sum());
+        }
+        Ok(report)
+    }
+
+    /// Remove the dated subfolders left empty by removing frames
+    fn remove_empty_folders(base: &Path, removed: &[PathBuf]) {
+        let mut folders: Vec<&Path> = removed.iter().filter_map(|path| path.parent()).collect();
+        folders.sort();
+        folders.dedup();
+        for folder in folders.iter() {
+            let mut folder = *folder;
+            while folder != base && folder.starts_with(base) {
+                if fs::remove_dir(folder).is_err() {
+                    break;
+                }
+                folder = match folder.parent() {
+                    Some(parent) => parent,
+                    None => break,
+                };
+            }
+        }
+    }
+
+    fn remove(
+        frames: Vec<StoredFrame>,
+        report: &mut RetentionReport,
+    ) -> Result<(), RetentionError> {
+        for frame in frames.into_iter() {
+            fs::remove_file(&frame.path)?;
+            report.freed += frame.size;
+            report.removed.push(frame.path);
+        }
+        Ok(())
+    }
+}
+
+pub mod error {
+    use crate::resources::ResourceError;
+    use std::error::Error;
+    use std::fmt;
+    use std::fmt::Display;
+    use std::io;
+
+    #[derive(Debug)]
+    pub enum RetentionError {
+        IoFailed(String),
+        InvalidPolicy(String),
+
+        /// Free space is below the minimum and the policy says to stop
+        OutOfSpace {
+            free: u64,
+            required: u64,
+        },
+    }
+
+    impl Display for RetentionError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                RetentionError::OutOfSpace { free, required } => write!(
+                    f,
+                    "out of disk space: {} MB free, {} MB required",
+                    free / 1024 / 1024,
+                    required / 1024 / 1024
+                ),
+                _ => write!(f, "{:?}", self),
+            }
+        }
+    }
+
+    impl Error for RetentionError {}
+
+    impl From<io::Error> for RetentionError {
+        fn from(err: io::Error) -> Self {
+            RetentionError::IoFailed(format!("{}", err))
+        }
+    }
+
+    impl From<ResourceError> for RetentionError {
+        fn from(err: ResourceError) -> Self {
+            RetentionError::IoFailed(format!("{:?}", err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LowSpaceAction, Retention, RetentionError, RetentionPolicy, MS_PER_DAY};
+    use crate::resources::ResourceFolder;
+    use std::fs;
+    use std::path::Path;
+
+    fn make_frames(folder: &str, timestamps: &[u128]) -> ResourceFolder {
+        let _ = fs::remove_dir_all(folder);
+        fs::create_dir_all(folder).unwrap();
+        for t in timestamps.iter() {
+            fs::write(format!("{}/{}-frame.png", folder, t), vec![0u8; 100]).unwrap();
+        }
+        fs::write(format!("{}/notes.txt", folder), vec![0u8; 100]).unwrap();
+        ResourceFolder::new(folder).require_existing().unwrap()
+    }
+
+    fn exists(folder: &str, t: u128) -> bool {
+        Path::new(&format!("{}/{}-frame.png", folder, t)).exists()
+    }
+
+    #[test]
+    pub fn test_age_and_thinning() {
+        let folder = "test/output/retention_age";
+        let now = 100 * MS_PER_DAY;
+        let hour = 60 * 60 * 1000;
+        let old = now - 40 * MS_PER_DAY;
+        let thin = now - 10 * MS_PER_DAY;
+        let output = make_frames(
+            folder,
+            &[
+                old,
+                thin,
+                thin + hour / 2,
+                thin + hour,
+                now - 1000,
+                now - 500,
+            ],
+        );
+        let retention = Retention::new(RetentionPolicy {
+            max_age_days: 30,
+            thin_after_days: 7,
+            thin_interval: hour as u64,
+            ..Default::default()
+        });
+
+        let report = retention.apply(&output, now).unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert!(!exists(folder, old));
+        assert!(exists(folder, thin));
+        assert!(!exists(folder, thin + hour / 2));
+        assert!(exists(folder, thin + hour));
+        assert!(exists(folder, now - 500));
+        assert!(Path::new(&format!("{}/notes.txt", folder)).exists());
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    pub fn test_total_size_and_low_space() {
+        let folder = "test/output/retention_size";
+        let output = make_frames(folder, &[1000, 2000, 3000, 4000]);
+        let retention = Retention::new(RetentionPolicy {
+            max_total_bytes: 250,
+            ..Default::default()
+        });
+        let report = retention.apply(&output, 5000).unwrap();
+        assert_eq!(report.total, Some(200));
+        assert!(!exists(folder, 1000));
+        assert!(!exists(folder, 2000));
+        assert!(exists(folder, 3000));
+
+        let retention = Retention::new(RetentionPolicy {
+            min_free_bytes: u64::MAX,
+            low_space: LowSpaceAction::Stop,
+            ..Default::default()
+        });
+        #[cfg(unix)]
+        match retention.apply(&output, 5000) {
+            Err(RetentionError::OutOfSpace { .. }) => {}
+            other => panic!("expected OutOfSpace, got {:?}", other),
+        }
+        assert!(exists(folder, 3000));
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    pub fn test_low_space_deletes_oldest() {
+        let folder = "test/output/retention_low_space";
+        let output = make_frames(folder, &[1000, 2000, 3000]);
+        fs::create_dir_all(format!("{}/rejected", folder)).unwrap();
+        fs::write(
+            format!("{}/rejected/500-0-dark.png", folder),
+            vec![0u8; 100],
+        )
+        .unwrap();
+
+        // No amount of deleting frees this much, so every frame goes before it gives up
+        let retention = Retention::new(RetentionPolicy {
+            min_free_bytes: u64::MAX,
+            low_space: LowSpaceAction::DeleteOldest,
+            ..Default::default()
+        });
+        #[cfg(unix)]
+        {
+            match retention.apply(&output, 5000) {
+                Err(RetentionError::OutOfSpace { .. }) => {}
+                other => panic!("expected OutOfSpace, got {:?}", other),
+            }
+            assert!(!exists(folder, 1000));
+            assert!(!exists(folder, 2000));
+            assert!(!exists(folder, 3000));
+        }
+        assert!(Path::new(&format!("{}/notes.txt", folder)).exists());
+        assert!(Path::new(&format!("{}/rejected/500-0-dark.png", folder)).exists());
+        fs::remove_dir_all(folder).unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(test)]
+use std::sync::{Mutex, MutexGuard};
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Catch SIGINT and SIGTERM so the capture can finish the frame it is working on and
+/// shut down cleanly; see `shutdown_requested`. A second signal exits immediately.
+#[cfg(unix)]
+pub fn install_shutdown_handler() -> std::io::Result<()> {
+    extern "C" fn handle(_signal: libc::c_int) {
+        if SHUTDOWN.swap(true, Ordering::SeqCst) {
+            unsafe { libc::_exit(130) };
+        }
+    }
+
+    let handler = handle as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    for signal in [libc::SIGINT, libc::SIGTERM].iter() {
+        if unsafe { libc::signal(*signal, handler) } == libc::SIG_ERR {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install_shutdown_handler() -> std::io::Result<()> {
+    Ok(())
+}
+
+/// True once SIGINT or SIGTERM has been received
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// Tests that set the flag, or run a capture that watches it, hold this so they don't
+/// stop each other
+#[cfg(test)]
+static SIGNAL_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[cfg(test)]
+pub(crate) fn lock_signals_for_test() -> MutexGuard<'static, ()> {
+    SIGNAL_TEST_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Set or clear the flag as the signal handler would
+#[cfg(test)]
+pub(crate) fn set_shutdown_requested(requested: bool) {
+    SHUTDOWN.store(requested, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lock_signals_for_test, set_shutdown_requested, shutdown_requested};
+    use crate::resources::CaptureControl;
+
+    #[test]
+    pub fn test_shutdown_stops_capture() {
+        let _signals = lock_signals_for_test();
+        let control = CaptureControl::new().with_signals();
+        let ignores_signals = CaptureControl::new();
+        assert!(!control.is_stopped());
+
+        set_shutdown_requested(true);
+        assert!(shutdown_requested());
+        assert!(control.is_stopped());
+        assert!(!ignores_signals.is_stopped());
+
+        set_shutdown_requested(false);
+        assert!(!control.is_stopped());
+    }
+}
@@ -1,6 +1,7 @@
 pub use self::error::TimeProbeError;
-use crate::resources::LockFile;
+use crate::resources::{CaptureControl, LockFile};
 use chrono::{DateTime, Utc};
+use std::path::Path;
 use std::thread::sleep;
 use std::time::{Duration, Instant, UNIX_EPOCH};
 
@@ -19,6 +20,9 @@ pub struct TimeProbeConfig {
 
     /// If the lock is provided, halt when the lock halts
     pub lock: Option<LockFile>,
+
+    /// If the control is provided, pause, stop, sample early or change interval on request
+    pub control: Option<CaptureControl>,
 }
 
 pub struct TimeProbe {
@@ -27,6 +31,7 @@ pub struct TimeProbe {
     moment: Instant,
     last: Instant,
     sampled: i64,
+    offset: u128,
 }
 
 #[derive(Debug)]
@@ -41,6 +46,27 @@ pub struct TimeSnapshot {
     pub utc: DateTime<Utc>,
 }
 
+impl TimeSnapshot {
+    /// A snapshot of the given time in ms since the epoch
+    pub fn from_timestamp(timestamp: u128) -> TimeSnapshot {
+        let d = UNIX_EPOCH + Duration::from_millis(timestamp as u64);
+        TimeSnapshot {
+            timestamp,
+            elapsed: 0,
+            utc: DateTime::<Utc>::from(d),
+        }
+    }
+
+    /// The capture time of a saved frame, from its `{timestamp}-{date}.png` filename
+    pub fn from_frame_path(path: &Path) -> Option<TimeSnapshot> {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('-').next())
+            .and_then(|prefix| str::parse::<u128>(prefix).ok())
+            .map(TimeSnapshot::from_timestamp)
+    }
+}
+
 impl TimeProbe {
     pub fn new(mut config: TimeProbeConfig) -> TimeProbe {
         if config.time_scale <= 0f32 {
@@ -52,6 +78,7 @@ impl TimeProbe {
             last: Instant::now(),
             reference: (chrono::Local::now().timestamp() as u128) * 1000,
             sampled: 0,
+            offset: 0,
         }
     }
 
@@ -73,11 +100,28 @@ impl TimeProbe {
         Ok(())
     }
 
+    /// Continue a session which started at started (ms since the epoch): elapsed counts from
+    /// then, and the next sample is one interval after last_sample, or straight away if that
+    /// time has passed. Call this after `sync_network_time`.
+    pub fn resume(&mut self, started: u128, last_sample: Option<u128>) {
+        let since_spawn_real = self.moment.elapsed().as_millis();
+        let since_spawn_scale = (since_spawn_real as f32 * self.config.time_scale).floor() as u128;
+        let now = self.reference + since_spawn_scale;
+        self.offset = self.reference.saturating_sub(started);
+        if let Some(last_sample) = last_sample {
+            let since_last_scale = now.saturating_sub(last_sample);
+            let since_last_real = (since_last_scale as f32 / self.config.time_scale) as u64;
+            if let Some(last) = Instant::now().checked_sub(Duration::from_millis(since_last_real)) {
+                self.last = last;
+            }
+        }
+    }
+
     fn as_snapshot(&self, ms_since_spawn: u128) -> TimeSnapshot {
         let d = UNIX_EPOCH + Duration::from_millis((self.reference + ms_since_spawn) as u64);
         TimeSnapshot {
             timestamp: self.reference + ms_since_spawn,
-            elapsed: ms_since_spawn,
+            elapsed: ms_since_spawn + self.offset,
             utc: DateTime::<Utc>::from(d),
         }
     }
@@ -92,10 +136,23 @@ impl Iterator for TimeProbe {
         }
         loop {
             let now = Instant::now();
+            let mut interval = self.config.interval;
+            let mut capture_now = false;
+            if let Some(control) = &self.config.control {
+                if control.is_stopped() {
+                    return None;
+                }
+                interval = control.interval().unwrap_or(interval);
+                capture_now = control.take_capture_now();
+                if control.is_paused() && !capture_now {
+                    // Start a full interval again once resumed
+                    self.last = now;
+                }
+            }
             let elapsed = now - self.last;
             let elapsed_real = elapsed.as_millis();
             let elapsed_scale = (elapsed_real as f32 * self.config.time_scale).floor() as u128;
-            if elapsed_scale > self.config.interval as u128 {
+            if capture_now || elapsed_scale > interval as u128 {
                 self.sampled += 1;
                 self.last = now;
                 let since_spawn_real = (now - self.moment).as_millis();
@@ -142,6 +199,9 @@ mod error {
 #[cfg(test)]
 mod tests {
     use crate::resources::time_probe::{TimeProbe, TimeProbeConfig, TimeSnapshot};
+    use crate::resources::{CaptureControl, ControlCommand};
+    use std::thread;
+    use std::time::{Duration, Instant};
 
     #[test]
     pub fn sample_at_interval() {
@@ -150,6 +210,8 @@ mod tests {
             idle: 100,
             samples: 4,
             time_scale: 1f32,
+            lock: None,
+            control: None,
         });
 
         let results: Vec<TimeSnapshot> = probe.collect();
@@ -167,6 +229,8 @@ mod tests {
             idle: 100,
             samples: 10,
             time_scale: 5f32,
+            lock: None,
+            control: None,
         });
 
         let results: Vec<TimeSnapshot> = probe.collect();
@@ -184,6 +248,8 @@ mod tests {
             idle: 100,
             samples: 2,
             time_scale: 1f32,
+            lock: None,
+            control: None,
         });
 
         probe.sync_network_time("pool.ntp.org").unwrap();
@@ -196,4 +262,54 @@ mod tests {
             assert_eq!((result.elapsed / 1000), (i + 1) as u128);
         }
     }
+
+    #[test]
+    pub fn sample_with_control() {
+        let control = CaptureControl::new();
+        let mut probe = TimeProbe::new(TimeProbeConfig {
+            interval: 60000,
+            idle: 10,
+            samples: -1,
+            time_scale: 1f32,
+            lock: None,
+            control: Some(control.clone()),
+        });
+
+        let start = Instant::now();
+        control.apply(ControlCommand::CaptureNow);
+        assert!(probe.next().is_some());
+
+        control.apply(ControlCommand::SetInterval(50));
+        assert!(probe.next().is_some());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let remote = control.clone();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            remote.apply(ControlCommand::Stop);
+        });
+        control.apply(ControlCommand::Pause);
+        assert!(probe.next().is_none());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    pub fn resume_session() {
+        let mut probe = TimeProbe::new(TimeProbeConfig {
+            interval: 500,
+            idle: 10,
+            samples: 2,
+            time_scale: 1f32,
+            lock: None,
+            control: None,
+        });
+        let now = probe.reference_time().timestamp_millis() as u128;
+        probe.resume(now - 3_600_000, Some(now - 400));
+
+        let start = Instant::now();
+        let sample = probe.next().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(400));
+        assert!(sample.elapsed >= 3_600_000);
+        assert!(probe.next().unwrap().elapsed >= sample.elapsed + 500);
+    }
 }